@@ -0,0 +1,93 @@
+#[macro_use]
+extern crate criterion;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+use std::time::Duration;
+
+use criterion::Criterion;
+use serde_json::json;
+
+use activeledger::{Connection, Transaction};
+
+/// Answer every request with a umid so the connection can send transactions
+fn serve() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            // Read the headers then the body so the connection closes cleanly
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                let header = header.to_lowercase();
+                if let Some(value) = header.strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            let body = r#"{"$umid": "umid"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    url
+}
+
+/// Build a transaction with roughly the given number of bytes of input
+fn transaction(size: usize) -> Transaction {
+    Transaction::builder()
+        .namespace("default")
+        .contract("contract")
+        .input("stream", json!({ "data": "a".repeat(size) }))
+        .build()
+        .unwrap()
+}
+
+/// Send a transaction of the given size to a local listener
+///
+/// Run with `--features rayon` to compare encrypting the chunks in parallel.
+fn encrypt_benchmark(name: &str, size: usize) {
+    let c: Criterion = Default::default();
+    let mut c = c.sample_size(10).measurement_time(Duration::new(30, 0));
+
+    let rsa = openssl::rsa::Rsa::generate(2048).unwrap();
+    let node_pem = String::from_utf8(rsa.public_key_to_pem().unwrap()).unwrap();
+
+    let connection = Connection::new_with_node_key(&serve(), &node_pem).unwrap();
+
+    // Sent to a local listener, so the time is mostly spent encrypting
+    c.bench_function(name, |b| {
+        b.iter_with_setup(
+            || transaction(size),
+            |tx| connection.send_transaction(tx).unwrap(),
+        )
+    });
+}
+
+fn encrypt_100kb_benchmark(_c: &mut Criterion) {
+    encrypt_benchmark("Encrypt 100 KB transaction", 100 * 1024);
+}
+
+fn encrypt_1mb_benchmark(_c: &mut Criterion) {
+    encrypt_benchmark("Encrypt 1 MB transaction", 1024 * 1024);
+}
+
+criterion_group!(benches, encrypt_100kb_benchmark, encrypt_1mb_benchmark);
+criterion_main!(benches);
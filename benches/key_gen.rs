@@ -5,7 +5,7 @@ use std::time::Duration;
 
 use criterion::Criterion;
 
-use activeledger::key::{ec::EllipticCurve, rsa::RSA};
+use activeledger::key::{EllipticCurve, RSA};
 
 fn rsa_benchmark(_c: &mut Criterion) {
     let c: Criterion = Default::default();
@@ -0,0 +1,44 @@
+#[macro_use]
+extern crate criterion;
+
+use std::time::Duration;
+
+use criterion::Criterion;
+
+use activeledger::key::{EllipticCurve, RSA};
+
+/// The number of messages signed in each iteration
+const MESSAGES: usize = 10_000;
+
+fn rsa_sign_benchmark(_c: &mut Criterion) {
+    let c: Criterion = Default::default();
+    let mut c = c.sample_size(10).measurement_time(Duration::new(30, 0));
+
+    let rsa = RSA::new("").unwrap();
+
+    c.bench_function("RSA sign 10k", |b| {
+        b.iter(|| {
+            for _ in 0..MESSAGES {
+                rsa.sign("Data to sign").unwrap();
+            }
+        })
+    });
+}
+
+fn ec_sign_benchmark(_c: &mut Criterion) {
+    let c: Criterion = Default::default();
+    let mut c = c.sample_size(10).measurement_time(Duration::new(30, 0));
+
+    let ec = EllipticCurve::new("").unwrap();
+
+    c.bench_function("EC sign 10k", |b| {
+        b.iter(|| {
+            for _ in 0..MESSAGES {
+                ec.sign("Data to sign").unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, rsa_sign_benchmark, ec_sign_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,57 @@
+#[macro_use]
+extern crate criterion;
+
+use std::time::Duration;
+
+use criterion::Criterion;
+
+use activeledger::key::{EllipticCurve, Pkcs8pem, RSA};
+
+/// The number of signatures verified in each iteration
+const SIGNATURES: usize = 10_000;
+
+fn rsa_verify_benchmark(_c: &mut Criterion) {
+    let c: Criterion = Default::default();
+    let mut c = c.sample_size(10).measurement_time(Duration::new(30, 0));
+
+    let rsa = RSA::new("").unwrap();
+    let signature = rsa.sign("Data to verify").unwrap();
+
+    // Keys read from storage usually only have the public PEM
+    let public = RSA::create_from_pem("", &Pkcs8pem::new("", &rsa.get_pem().unwrap().public));
+
+    c.bench_function("RSA verify 10k", |b| {
+        b.iter(|| {
+            for _ in 0..SIGNATURES {
+                rsa.verify("Data to verify", &signature).unwrap();
+            }
+        })
+    });
+
+    c.bench_function("RSA public verify 10k", |b| {
+        b.iter(|| {
+            for _ in 0..SIGNATURES {
+                public.verify("Data to verify", &signature).unwrap();
+            }
+        })
+    });
+}
+
+fn ec_verify_benchmark(_c: &mut Criterion) {
+    let c: Criterion = Default::default();
+    let mut c = c.sample_size(10).measurement_time(Duration::new(30, 0));
+
+    let ec = EllipticCurve::new("").unwrap();
+    let signature = ec.sign("Data to verify").unwrap();
+
+    c.bench_function("EC verify 10k", |b| {
+        b.iter(|| {
+            for _ in 0..SIGNATURES {
+                ec.verify("Data to verify", &signature).unwrap();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, rsa_verify_benchmark, ec_verify_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,332 @@
+/*
+ * MIT License (MIT)
+ * Copyright (c) 2019 Activeledger
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! # Canonical JSON
+//!
+//! Activeledger nodes verify a signature against the `$tx` object as the node
+//! serializes it with JavaScript's `JSON.stringify()`. The node keeps the order the
+//! keys were received in, so the SDK must send `$tx` in exactly the form it signed.
+//!
+//! This module serializes JSON in a canonical form that survives being parsed
+//! and serialized again by the node:
+//! * No insignificant whitespace.
+//! * Object keys that are array indexes (`"0"`, `"1"`, `"42"`) come first in
+//!   numeric order, as JavaScript always orders them that way. The other keys
+//!   follow, sorted by their bytes.
+//! * Numbers are formatted as JavaScript formats them, `1.0` becomes `1`,
+//!   `1e21` becomes `1e+21` and `1e-7` becomes `1e-7`.
+//!   Integers larger than 2^53 are rounded to the nearest double first, as the
+//!   node can't represent them exactly, so `9007199254740993` becomes `9007199254740992`.
+//! * Strings are escaped as `JSON.stringify()` escapes them, only `"`, `\` and
+//!   control characters are escaped.
+//!
+//! `Transaction::sign()` uses this form for both the signed data and the
+//! transaction that is sent. Use `canonical::sign()` when building `$sigs` by hand.
+//!
+//! ## Other SDKs
+//! The TypeScript SDK signs `JSON.stringify($tx)` with the keys in the order they
+//! were inserted and sends that same object, so the node verifies the bytes it signed.
+//! Both SDKs produce signatures the node accepts, they don't need to agree on key order.
+//!
+//! The escaping and number formatting here are byte-identical to `JSON.stringify()`.
+//! The output matches the TypeScript SDK exactly when its object keys were
+//! inserted in the order used here. `serde_json` doesn't keep insertion order,
+//! so a signature made by another SDK can only be checked against a `Value`
+//! if the keys were signed in this order.
+//!
+//! ## Example
+//! ```
+//! # use activeledger::canonical;
+//! let value = serde_json::json!({ "b": 1.0, "a": [true, null], "10": "x", "2": "y" });
+//!
+//! let json = canonical::to_string(&value);
+//!
+//! assert_eq!(json, r#"{"2":"y","10":"x","a":[true,null],"b":1}"#);
+//! ```
+
+use serde_json::{Number, Value};
+
+use crate::key::{Key, KeyResult};
+
+/// The largest integer a JavaScript number holds exactly, 2^53
+const MAX_SAFE_INTEGER: u64 = 1 << 53;
+
+/// Serialize a JSON value in canonical form
+pub fn to_string(value: &Value) -> String {
+    let mut output = String::new();
+    write_value(value, &mut output);
+    output
+}
+
+/// Sign a JSON value in canonical form
+///
+/// Signs the same data as `Transaction::sign()` when given the `$tx` object.
+///
+/// # Example
+/// ```
+/// # use activeledger::{canonical, key::RSA};
+/// let key = RSA::new("keyname").unwrap();
+/// let tx = serde_json::json!({ "$namespace": "default", "$contract": "contract", "$i": {} });
+///
+/// let signature = canonical::sign(&key, &tx).unwrap();
+/// ```
+///
+/// ## Errors
+/// Returns the `KeyError` from signing.
+pub fn sign(key: &dyn Key, value: &Value) -> KeyResult<String> {
+    key.sign(&to_string(value))
+}
+
+/// Append a value to the output
+fn write_value(value: &Value, output: &mut String) {
+    match value {
+        Value::Null => output.push_str("null"),
+        Value::Bool(true) => output.push_str("true"),
+        Value::Bool(false) => output.push_str("false"),
+        Value::Number(number) => output.push_str(&format_number(number)),
+        Value::String(string) => write_string(string, output),
+        Value::Array(array) => {
+            output.push('[');
+
+            for (index, item) in array.iter().enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+
+                write_value(item, output);
+            }
+
+            output.push(']');
+        }
+        Value::Object(object) => {
+            // Array index keys first in numeric order, the map keeps the others sorted
+            let mut index_keys: Vec<(u32, &String)> = object
+                .keys()
+                .filter_map(|key| array_index(key).map(|index| (index, key)))
+                .collect();
+            index_keys.sort();
+
+            let other_keys = object.keys().filter(|key| array_index(key).is_none());
+
+            let keys = index_keys.into_iter().map(|(_, key)| key).chain(other_keys);
+
+            output.push('{');
+
+            for (index, key) in keys.enumerate() {
+                if index > 0 {
+                    output.push(',');
+                }
+
+                write_string(key, output);
+                output.push(':');
+                write_value(&object[key], output);
+            }
+
+            output.push('}');
+        }
+    }
+}
+
+/// Append an escaped string to the output
+fn write_string(string: &str, output: &mut String) {
+    output.push('"');
+
+    for character in string.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\u{08}' => output.push_str("\\b"),
+            '\u{0c}' => output.push_str("\\f"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            character if (character as u32) < 0x20 => {
+                output.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => output.push(character),
+        }
+    }
+
+    output.push('"');
+}
+
+/// Get the index if the key is an array index as defined by JavaScript
+fn array_index(key: &str) -> Option<u32> {
+    if key.is_empty() || (key.len() > 1 && key.starts_with('0')) {
+        return None;
+    }
+
+    if !key.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    // The largest array index is 2^32 - 2
+    match key.parse::<u32>() {
+        Ok(index) if index < u32::MAX => Some(index),
+        _ => None,
+    }
+}
+
+/// Format a number the same way as JavaScript
+fn format_number(number: &Number) -> String {
+    // Larger integers are rounded by the node, format them as a double
+    if let Some(integer) = number.as_i64() {
+        if integer.unsigned_abs() <= MAX_SAFE_INTEGER {
+            return integer.to_string();
+        }
+    }
+
+    if let Some(integer) = number.as_u64() {
+        if integer <= MAX_SAFE_INTEGER {
+            return integer.to_string();
+        }
+    }
+
+    match number.as_f64() {
+        Some(float) => format_float(float),
+        None => number.to_string(),
+    }
+}
+
+/// Format a float using the ECMAScript Number::toString rules
+fn format_float(float: f64) -> String {
+    if float == 0.0 {
+        return String::from("0");
+    }
+
+    // The shortest digits that round trip and their exponent, for example 1.25e2
+    let scientific = format!("{:e}", float.abs());
+    let (mantissa, exponent) = match scientific.split_once('e') {
+        Some(parts) => parts,
+        None => return float.to_string(),
+    };
+
+    let digits = mantissa.replace('.', "");
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+
+    // k is the number of digits and n the position of the decimal point
+    let k = digits.len() as i32;
+    let n = exponent + 1;
+
+    let formatted = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        let (integer, fraction) = digits.split_at(n as usize);
+        format!("{}.{}", integer, fraction)
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        let sign = if n > 0 { "+" } else { "-" };
+        let exponent = (n - 1).abs();
+
+        if k == 1 {
+            format!("{}e{}{}", digits, sign, exponent)
+        } else {
+            let (first, rest) = digits.split_at(1);
+            format!("{}.{}e{}{}", first, rest, sign, exponent)
+        }
+    };
+
+    if float < 0.0 {
+        format!("-{}", formatted)
+    } else {
+        formatted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn canonical_keys() {
+        let value = json!({
+            "$tx": { "b": 1, "a": 2 },
+            "10": 0,
+            "2": 0,
+            "01": 0,
+            "$sigs": {}
+        });
+
+        assert_eq!(
+            to_string(&value),
+            r#"{"2":0,"10":0,"$sigs":{},"$tx":{"a":2,"b":1},"01":0}"#
+        );
+    }
+
+    #[test]
+    fn canonical_numbers() {
+        let numbers = json!([1.0, -1.5, 0.1, 123.456, 1e21, 1e-7, 0.000001, -0.0, 42, -7]);
+
+        assert_eq!(
+            to_string(&numbers),
+            "[1,-1.5,0.1,123.456,1e+21,1e-7,0.000001,0,42,-7]"
+        );
+    }
+
+    #[test]
+    fn canonical_numbers_javascript() {
+        // Expected values are the output of JSON.stringify() in Node.js
+        let numbers = json!([
+            0.30000000000000004,
+            5e-324,
+            1.7976931348623157e308,
+            1.2345678901234568e20,
+            9007199254740992_u64,
+            9007199254740993_u64,
+            -9007199254740993_i64,
+            u64::MAX,
+            i64::MIN
+        ]);
+
+        assert_eq!(
+            to_string(&numbers),
+            "[0.30000000000000004,5e-324,1.7976931348623157e+308,123456789012345680000,\
+             9007199254740992,9007199254740992,-9007199254740992,18446744073709552000,\
+             -9223372036854776000]"
+        );
+    }
+
+    #[test]
+    fn canonical_sign() {
+        let key = crate::key::EllipticCurve::new("Test").unwrap();
+        let value = json!({ "b": 1.0, "a": "\u{2028}" });
+
+        let signature = sign(&key, &value).unwrap();
+
+        assert!(key
+            .verify("{\"a\":\"\u{2028}\",\"b\":1}", &signature)
+            .unwrap());
+    }
+
+    #[test]
+    fn canonical_strings() {
+        let value = json!("quote \" slash \\ / newline \n bell \u{07} unicode é");
+
+        assert_eq!(
+            to_string(&value),
+            r#""quote \" slash \\ / newline \n bell \u0007 unicode é""#
+        );
+    }
+}
@@ -0,0 +1,341 @@
+/*
+ * MIT License (MIT)
+ * Copyright (c) 2019 Activeledger
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::{HeaderName, HeaderValue};
+
+use super::connection::{
+    ClientOptions, Connection, OaepHash, DEFAULT_MAX_RESPONSE_SIZE, DEFAULT_STATUS_PATH,
+};
+use super::error::{ConnectionError::HttpError, ConnectionResult};
+
+/// Builder for a Connection with non-default options
+///
+/// # Example
+/// ```
+/// # use activeledger::Connection;
+/// let connection = Connection::builder()
+///     .url("http://localhost:5260")
+///     .encrypt(true)
+///     .gzip(true)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Default)]
+pub struct ConnectionBuilder {
+    urls: Vec<String>,
+    encrypt: bool,
+    gzip: bool,
+    client: Option<reqwest::Client>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    max_idle: Option<usize>,
+    proxy: Option<String>,
+    status_path: Option<String>,
+    oaep_hash: OaepHash,
+    events_url: Option<String>,
+    max_response_size: Option<u64>,
+    identity: Option<(Vec<u8>, String)>,
+    encrypt_header: Option<(String, String)>,
+    lazy: bool,
+}
+
+impl ConnectionBuilder {
+    /// Create a new builder with no nodes
+    ///
+    /// `Connection::builder()` can also be used.
+    pub fn new() -> ConnectionBuilder {
+        ConnectionBuilder::default()
+    }
+
+    /// Add a node to the connection
+    ///
+    /// Adding more than one node creates a pool, see `Connection::new_pool()`.
+    pub fn url(mut self, url: &str) -> ConnectionBuilder {
+        self.urls.push(url.to_string());
+        self
+    }
+
+    /// Encrypt transactions sent by the connection, defaults to false
+    pub fn encrypt(mut self, encrypt: bool) -> ConnectionBuilder {
+        self.encrypt = encrypt;
+        self
+    }
+
+    /// Set the header that marks a transaction as encrypted
+    ///
+    /// Defaults to `X-Activeledger-Encrypt: 1`, which is what Activeledger nodes
+    /// look for. Only change it for gateways that rename the header.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let connection = Connection::builder()
+    ///     .url("http://localhost:5260")
+    ///     .encrypt(true)
+    ///     .encrypt_header("X-Gateway-Encrypt", "true")
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn encrypt_header(mut self, name: &str, value: &str) -> ConnectionBuilder {
+        self.encrypt_header = Some((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Don't contact the nodes when building, defaults to false
+    ///
+    /// Normally `build()` fetches the node keys when encrypting and checks that
+    /// a node can be reached. A lazy connection skips both, the node key is
+    /// fetched and cached when the first encrypted transaction is sent, so a
+    /// node that is briefly unavailable at startup doesn't stop the connection
+    /// being created. Use `Connection::has_node_key()` to see if it has been fetched.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let connection = Connection::builder()
+    ///     .url("http://localhost:5260")
+    ///     .encrypt(true)
+    ///     .lazy(true)
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// assert!(!connection.has_node_key());
+    /// ```
+    pub fn lazy(mut self, lazy: bool) -> ConnectionBuilder {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Gzip transactions sent by the connection, defaults to false
+    ///
+    /// The request is sent with `Content-Encoding: gzip` so the node must support it.
+    /// When also encrypting, the encrypted data is compressed.
+    pub fn gzip(mut self, gzip: bool) -> ConnectionBuilder {
+        self.gzip = gzip;
+        self
+    }
+
+    /// Use the given HTTP client instead of creating one
+    ///
+    /// See `Connection::with_client()`.
+    pub fn client(mut self, client: reqwest::Client) -> ConnectionBuilder {
+        self.client = Some(client);
+        self
+    }
+
+    /// Timeout for requests made by the connection, including fetching the node key
+    ///
+    /// Defaults to reqwest's timeout of 30 seconds. When encrypting, the node key is
+    /// fetched while the connection is built, a node that doesn't respond in time
+    /// fails with EncryptionError 4009. A transaction whose response takes too long
+    /// fails with HttpError 1006 and is not sent to another node, as the node may
+    /// already have received it.
+    /// The timeout is ignored when a client is given, set it on the client instead.
+    pub fn timeout(mut self, timeout: Duration) -> ConnectionBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout for connecting to a node
+    ///
+    /// Allows unreachable nodes to fail quickly while the request timeout leaves
+    /// contracts time to commit. A transaction that can't connect in time fails
+    /// with HttpError 1005 and is sent to the next node.
+    /// By default only the request timeout applies. Like the timeout, this is
+    /// ignored when a client is given.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// # use std::time::Duration;
+    /// let connection = Connection::builder()
+    ///     .url("http://localhost:5260")
+    ///     .connect_timeout(Duration::from_secs(2))
+    ///     .timeout(Duration::from_secs(60))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn connect_timeout(mut self, timeout: Duration) -> ConnectionBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// The most idle connections to keep open to each node
+    ///
+    /// Connections are kept open after a transaction so the next one can reuse
+    /// them. Set to 0 to close each connection once its response has been read.
+    /// By default there is no limit. Like the timeout, this is ignored when a
+    /// client is given.
+    pub fn max_idle_connections(mut self, max: usize) -> ConnectionBuilder {
+        self.max_idle = Some(max);
+        self
+    }
+
+    /// Send all requests through the given proxy
+    ///
+    /// Without an explicit proxy the `HTTP_PROXY` and `HTTPS_PROXY` environment
+    /// variables are used. Like the timeout, this is ignored when a client is given.
+    pub fn proxy(mut self, url: &str) -> ConnectionBuilder {
+        self.proxy = Some(url.to_string());
+        self
+    }
+
+    /// Set the path of the node status endpoint, defaults to `/a/status`
+    ///
+    /// The status endpoint provides the node key used for encryption and is used
+    /// to check that the node can be reached. Set this when the node is behind a
+    /// gateway that mounts it under a prefix, for example `/ledger/a/status`.
+    /// The path is joined to each node URL.
+    pub fn status_path(mut self, path: &str) -> ConnectionBuilder {
+        self.status_path = Some(path.to_string());
+        self
+    }
+
+    /// Set the digest used for OAEP padding when encrypting, defaults to SHA-1
+    ///
+    /// Activeledger nodes decrypt transactions with SHA-1 OAEP, only change this
+    /// for nodes that have been configured to expect SHA-256.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{Connection, OaepHash};
+    /// let connection = Connection::builder()
+    ///     .url("http://localhost:5260")
+    ///     .encrypt(true)
+    ///     .oaep_hash(OaepHash::Sha256)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn oaep_hash(mut self, hash: OaepHash) -> ConnectionBuilder {
+        self.oaep_hash = hash;
+        self
+    }
+
+    /// Present a TLS client certificate to nodes that require mutual TLS
+    ///
+    /// Takes a PKCS#12 bundle holding the certificate and its private key, and
+    /// the bundle's passphrase. The certificate is used for every request made by
+    /// the connection. Like the timeout, this is ignored when a client is given.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let bundle = std::fs::read("/path/to/client.p12").unwrap();
+    /// # let bundle = std::fs::read("./testfiles/client.p12").unwrap();
+    ///
+    /// let connection = Connection::builder()
+    ///     .url("https://localhost:5260")
+    ///     .client_identity(&bundle, "passphrase")
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn client_identity(mut self, pkcs12_der: &[u8], passphrase: &str) -> ConnectionBuilder {
+        self.identity = Some((pkcs12_der.to_vec(), passphrase.to_string()));
+        self
+    }
+
+    /// Set the Activecore URL used by `Connection::subscribe_url()`
+    ///
+    /// Defaults to the URL of the node in use.
+    pub fn events_url(mut self, url: &str) -> ConnectionBuilder {
+        self.events_url = Some(url.to_string());
+        self
+    }
+
+    /// Set the largest transaction response, in bytes, that will be read
+    ///
+    /// Defaults to 4 MiB. Larger responses return ResponseError 3007 instead of
+    /// being held in memory. The limit doesn't apply to
+    /// `Connection::send_transaction_streaming()`, which leaves reading the
+    /// response to the caller.
+    pub fn max_response_size(mut self, bytes: u64) -> ConnectionBuilder {
+        self.max_response_size = Some(bytes);
+        self
+    }
+
+    /// Create the connection
+    ///
+    /// ## Errors
+    /// The same errors as `Connection::new_pool()` are returned, UrlError 2002
+    /// if the proxy URL is invalid and HttpError 1004 if the client identity
+    /// can't be read. HttpError 1007 is returned if the encryption header is not
+    /// a valid header name and value.
+    pub fn build(self) -> ConnectionResult<Connection> {
+        let urls: Vec<&str> = self.urls.iter().map(String::as_str).collect();
+
+        let encrypt_header = match &self.encrypt_header {
+            Some((name, value)) => Some(ConnectionBuilder::parse_header(name, value)?),
+            None => None,
+        };
+
+        let client = match self.client {
+            Some(client) => client,
+            None => {
+                let identity = self
+                    .identity
+                    .as_ref()
+                    .map(|(der, passphrase)| (der.as_slice(), passphrase.as_str()));
+
+                Connection::default_client(ClientOptions {
+                    timeout: self.timeout,
+                    connect_timeout: self.connect_timeout,
+                    proxy: self.proxy.as_deref(),
+                    identity,
+                    max_idle: self.max_idle,
+                })?
+            }
+        };
+
+        let status_path = self.status_path.as_deref().unwrap_or(DEFAULT_STATUS_PATH);
+
+        let mut connection =
+            Connection::create(urls, self.encrypt, client, status_path, self.lazy)?;
+
+        connection.gzip = self.gzip;
+        connection.oaep_hash = self.oaep_hash;
+        connection.events_url = self.events_url;
+        connection.max_response_size = self.max_response_size.unwrap_or(DEFAULT_MAX_RESPONSE_SIZE);
+
+        if let Some(encrypt_header) = encrypt_header {
+            connection.encrypt_header = encrypt_header;
+        }
+
+        Ok(connection)
+    }
+
+    /// Check the encryption header can be sent
+    fn parse_header(name: &str, value: &str) -> ConnectionResult<(HeaderName, HeaderValue)> {
+        let name = match HeaderName::from_bytes(name.as_bytes()) {
+            Ok(name) => name,
+            Err(error) => return Err(HttpError(1007, Some(Arc::new(error)))),
+        };
+
+        match HeaderValue::from_str(value) {
+            Ok(value) => Ok((name, value)),
+            Err(error) => Err(HttpError(1007, Some(Arc::new(error)))),
+        }
+    }
+}
@@ -21,20 +21,39 @@
  * SOFTWARE.
  */
 
-use base64;
-use openssl;
-use reqwest;
-use serde_json;
+use std::fmt;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use base64::{decode, encode};
+use base64::decode;
 
-use openssl::{pkey::PKey, rsa::Padding};
+use flate2::{write::GzEncoder, Compression};
 
+use log::{debug, info, warn};
+
+use openssl::{
+    encrypt::Encrypter,
+    hash::MessageDigest,
+    pkey::{PKey, Public},
+    rsa::Padding,
+};
+
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Response,
+};
+
+use super::builder::ConnectionBuilder;
 use super::error::{
-    ConnectionError::{EncryptionError, HttpError, ResponseError},
-    ConnectionResult,
+    ConnectionError::{self, EncodingError, EncryptionError, HttpError, ResponseError, UrlError},
+    ConnectionResult, ErrorSource, Redirect, UnexpectedResponse,
 };
+use super::response::{Streams, TimedResponse, TransactionResponse};
 
+use crate::key::Key;
 use crate::Transaction;
 
 /// # Connection
@@ -116,19 +135,204 @@ use crate::Transaction;
 /// // Send the transaction to the node specified when creating the connection
 /// connection.send_transaction(transaction).unwrap();
 /// ```
+///
+/// ### Connect to multiple nodes
+/// ```
+/// # use activeledger::Connection;
+/// // Transactions are sent to the next node if one can't be reached
+/// let urls = vec!["http://localhost:5260", "http://localhost:5270", "http://localhost:5280"];
+///
+/// let connection = Connection::new_pool(urls, false).unwrap();
+/// ```
+///
+/// ## Transport
+/// Transactions are always sent with an HTTP POST. Activeledger nodes only accept
+/// transactions over HTTP, the node's socket endpoints are for subscribing to events
+/// (see the active_events crate), not for submitting transactions.
+/// The body is always JSON, nodes parse transactions with `JSON.parse` and don't
+/// accept other encodings such as msgpack. Use the builder's `gzip` option to
+/// reduce the size of large transactions.
+/// To reduce the per request overhead reuse one Connection, its HTTP client keeps
+/// connections to the node open between transactions.
+///
+/// ## Namespaces
+/// Nodes don't provide an endpoint that lists their namespaces, so a namespace
+/// can't be checked before a transaction is sent. A transaction for a namespace or
+/// contract the node doesn't have is rejected, `TransactionResponse::check()`
+/// reports this as ResponseError 3008 with the node's error as the source.
+///
+/// ## Redirects
+/// Redirects are not followed, following one would turn the transaction POST into
+/// a GET and lose the transaction. A redirect, such as from a proxy that moves
+/// `http` to `https`, returns ResponseError 3006 with the redirect's location as
+/// the error's source. This only applies to the default client, a client given to
+/// the connection uses its own redirect policy.
+///
+/// ## Sharing
+/// Connection is `Send` and `Sync`, so it can be shared between threads in an `Arc`.
+/// It is also cheap to clone, clones share the node list and the HTTP client's
+/// connection pool.
+///
+/// ## Shutdown
+/// Sending is blocking, a transaction has been sent and its response read by the
+/// time `send_transaction()` returns, so there are no pending requests to wait for.
+/// A connection being used by another thread can't be dropped until that thread is
+/// done with it. Idle connections to the nodes are kept open for reuse and closed
+/// when the connection and all of its clones are dropped. To close them after
+/// each transaction set the builder's `max_idle_connections` to 0.
+///
+/// ## Encryption
+/// When encrypting, each node's public key is fetched when the connection is
+/// created and cached for the life of the connection. If a node's key changes
+/// transactions encrypted with the old key can't be decrypted by the node,
+/// call `refresh_node_key()` to fetch the keys again.
+///
+/// Transactions are encrypted with RSA OAEP using SHA-1, which is what Activeledger
+/// nodes decrypt with. Use the builder's `oaep_hash` option for nodes that expect
+/// a different digest.
 pub struct Connection {
-    url: String,
+    nodes: Arc<Vec<Node>>,
+    active_node: AtomicUsize,
     encrypt: bool,
-    node_key_data: Option<NodeKeyData>,
+    pub(super) gzip: bool,
+    pub(super) oaep_hash: OaepHash,
+    pub(super) events_url: Option<String>,
+    pub(super) max_response_size: u64,
+    pub(super) encrypt_header: (HeaderName, HeaderValue),
+    client: reqwest::Client,
+}
+
+/// The digest used for RSA OAEP padding when encrypting transactions
+///
+/// The same digest is used for OAEP and MGF1. Activeledger nodes decrypt using
+/// the Node.js `crypto` default of SHA-1, only use SHA-256 with nodes that have
+/// been changed to expect it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OaepHash {
+    /// SHA-1, used by default and by current Activeledger nodes
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl OaepHash {
+    fn digest(self) -> MessageDigest {
+        match self {
+            OaepHash::Sha1 => MessageDigest::sha1(),
+            OaepHash::Sha256 => MessageDigest::sha256(),
+        }
+    }
+}
+
+/// The largest transaction response that is read by default, 4 MiB
+pub(super) const DEFAULT_MAX_RESPONSE_SIZE: u64 = 4 * 1024 * 1024;
+
+/// The path of the node status endpoint, which also provides the node key
+pub(super) const DEFAULT_STATUS_PATH: &str = "/a/status";
+
+/// Options for the HTTP client created when one isn't given
+#[derive(Default)]
+pub(super) struct ClientOptions<'a> {
+    /// Request timeout, reqwest's default of 30 seconds is used when not set
+    pub timeout: Option<Duration>,
+    /// Connect timeout, only the request timeout applies when not set
+    pub connect_timeout: Option<Duration>,
+    /// Used before the `HTTP_PROXY` and `HTTPS_PROXY` environment variables
+    pub proxy: Option<&'a str>,
+    /// A PKCS#12 bundle and its passphrase
+    pub identity: Option<(&'a [u8], &'a str)>,
+    /// Idle connections kept open for each node, unlimited when not set
+    pub max_idle: Option<usize>,
+}
+
+/// The number of transaction bytes encrypted into each RSA block
+const ENCRYPT_CHUNK_SIZE: usize = 100;
+
+/// How many times a missing node key is requested before sending fails
+const NODE_KEY_ATTEMPTS: u32 = 2;
+
+/// The pause between node key requests
+const NODE_KEY_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// The header sent with encrypted transactions, `X-Activeledger-Encrypt: 1`
+const DEFAULT_ENCRYPT_HEADER: (&str, &str) = ("x-activeledger-encrypt", "1");
+
+/// Node struct
+/// A node the connection can send transactions to
+struct Node {
+    url: String,
+    status_url: String,
+    key_data: RwLock<Option<NodeKeyData>>,
+}
+
+impl Node {
+    /// Create a node, joining the status path to its URL
+    fn new(url: &str, status_path: &str) -> Node {
+        Node {
+            url: url.to_string(),
+            status_url: format!(
+                "{}/{}",
+                url.trim_end_matches('/'),
+                status_path.trim_start_matches('/')
+            ),
+            key_data: RwLock::new(None),
+        }
+    }
+
+    /// Get the cached node key, None if it hasn't been fetched
+    fn key_data(&self) -> RwLockReadGuard<'_, Option<NodeKeyData>> {
+        self.key_data.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace the cached node key
+    fn set_key_data(&self, key_data: NodeKeyData) {
+        let mut cached = self
+            .key_data
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *cached = Some(key_data);
+    }
 }
 
 /// NodeKeyData struct
 /// Used when sending encrypted transactions
+#[derive(Clone)]
 struct NodeKeyData {
     _encryption: String, // Future usage if Activeledger provides multiple encryption keys
     pem: String,
 }
 
+impl Clone for Connection {
+    fn clone(&self) -> Connection {
+        Connection {
+            nodes: Arc::clone(&self.nodes),
+            active_node: AtomicUsize::new(self.active_node.load(Ordering::Relaxed)),
+            encrypt: self.encrypt,
+            gzip: self.gzip,
+            oaep_hash: self.oaep_hash,
+            events_url: self.events_url.clone(),
+            max_response_size: self.max_response_size,
+            encrypt_header: self.encrypt_header.clone(),
+            client: self.client.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for Connection {
+    /// Shows the node URLs and options, the cached node keys are left out
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let urls: Vec<&str> = self.nodes.iter().map(|node| node.url.as_str()).collect();
+
+        f.debug_struct("Connection")
+            .field("urls", &urls)
+            .field("url", &self.url())
+            .field("encrypt", &self.encrypt)
+            .field("gzip", &self.gzip)
+            .finish()
+    }
+}
+
 // Public functions
 
 impl Connection {
@@ -145,26 +349,150 @@ impl Connection {
     /// let connection = Connection::new("http://localhost:5260", true).unwrap();
     /// ```
     pub fn new(url: &str, encrypt: bool) -> ConnectionResult<Connection> {
-        let mut node_key_data = None;
+        Connection::new_pool(vec![url], encrypt)
+    }
 
-        // If encrypt is true we should get the key data now instead of each tx run
-        if encrypt {
-            node_key_data = Some(Connection::get_node_key_data(url)?);
-        }
+    /// Create a new Connection to a group of Activeledger nodes
+    ///
+    /// Transactions are sent to the last node that could be reached, if it can't be
+    /// reached the next node in the list is tried until one responds.
+    /// Only connection failures move on to the next node, if a node responds with an
+    /// error that error is returned. Timeouts and errors after the transaction was
+    /// sent are returned too, so a transaction is never submitted twice.
+    ///
+    /// Creating the connection succeeds if any of the nodes respond.
+    /// When encrypting, nodes that can't be reached are asked for their key
+    /// when a transaction is sent to them.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let urls = vec!["http://localhost:5260", "http://localhost:5270"];
+    ///
+    /// let connection = Connection::new_pool(urls, false).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns UrlError 2001 if no URLs are given and HttpError 1001 if none of the nodes respond.
+    pub fn new_pool(urls: Vec<&str>, encrypt: bool) -> ConnectionResult<Connection> {
+        let client = Connection::default_client(ClientOptions::default())?;
 
-        let connection = Connection {
-            url: url.to_string(),
+        Connection::create(urls, encrypt, client, DEFAULT_STATUS_PATH, false)
+    }
+
+    /// Create a new Connection that uses the given HTTP client
+    ///
+    /// The client is used for all requests made by the connection, including
+    /// getting the node key when encrypting. This allows the client to be
+    /// configured with proxies, timeouts or connection limits.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let client = reqwest::Client::builder().build().unwrap();
+    ///
+    /// let connection = Connection::with_client("http://localhost:5260", false, client).unwrap();
+    /// ```
+    pub fn with_client(
+        url: &str,
+        encrypt: bool,
+        client: reqwest::Client,
+    ) -> ConnectionResult<Connection> {
+        Connection::create(vec![url], encrypt, client, DEFAULT_STATUS_PATH, false)
+    }
+
+    /// Create a new Connection without contacting the node
+    ///
+    /// The connection is not tested and, when encrypting, the node key is fetched
+    /// and cached when the first transaction is sent. This allows a Connection to be created
+    /// in tests that don't have a node available.
+    /// Sending a transaction fails if the node can't be reached.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let connection = Connection::new_unchecked("http://localhost:5260", true).unwrap();
+    /// ```
+    pub fn new_unchecked(url: &str, encrypt: bool) -> ConnectionResult<Connection> {
+        let client = Connection::default_client(ClientOptions::default())?;
+
+        Ok(Connection {
+            nodes: Arc::new(vec![Node::new(url, DEFAULT_STATUS_PATH)]),
+            active_node: AtomicUsize::new(0),
             encrypt,
-            node_key_data,
+            gzip: false,
+            oaep_hash: OaepHash::default(),
+            events_url: None,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            encrypt_header: Connection::default_encrypt_header(),
+            client,
+        })
+    }
+
+    /// Create a new encrypting Connection using a node key that is already known
+    ///
+    /// The node's status endpoint is never contacted, the given public PEM is
+    /// used to encrypt every transaction. This suits pre-provisioned setups, nodes
+    /// whose status endpoint can't be reached, and pinning the key. Like
+    /// `new_unchecked()` the connection is not tested. Calling `refresh_node_key()`
+    /// replaces the given key with the one the node provides.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// # let rsa = openssl::rsa::Rsa::generate(2048).unwrap();
+    /// # let node_pem = String::from_utf8(rsa.public_key_to_pem().unwrap()).unwrap();
+    /// let connection = Connection::new_with_node_key("http://localhost:5260", &node_pem).unwrap();
+    ///
+    /// assert!(connection.has_node_key());
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns EncryptionError 4005 if the PEM can't be parsed and 4006 if it
+    /// is not an RSA public key.
+    pub fn new_with_node_key(url: &str, pem: &str) -> ConnectionResult<Connection> {
+        // Check the key can be used for encryption before accepting it
+        let key = match PKey::public_key_from_pem(pem.as_bytes()) {
+            Ok(key) => key,
+            Err(error) => return Err(EncryptionError(4005, Some(Arc::new(error)))),
         };
 
-        // If connection test successful return Ok
-        Connection::test_connection(&connection)?;
+        if let Err(error) = key.rsa() {
+            return Err(EncryptionError(4006, Some(Arc::new(error))));
+        }
+
+        let connection = Connection::new_unchecked(url, true)?;
+
+        // Stored base64 encoded, as the node provides it
+        connection.nodes[0].set_key_data(NodeKeyData {
+            _encryption: String::from("rsa"),
+            pem: base64::encode(pem),
+        });
 
         Ok(connection)
     }
 
+    /// Create a ConnectionBuilder to set options that the constructors don't cover
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let connection = Connection::builder()
+    ///     .url("http://localhost:5260")
+    ///     .gzip(true)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> ConnectionBuilder {
+        ConnectionBuilder::new()
+    }
+
     /// Send a transaction via this connection
+    ///
+    /// The transaction is committed if the network reaches consensus.
+    /// Activeledger does not have a dry run mode, to test a contract
+    /// send the transaction to a staging network.
+    ///
     /// # Example
     /// ```
     /// # use activeledger::{Connection, Transaction};
@@ -198,133 +526,1261 @@ impl Connection {
     /// let response = connection.send_transaction(transaction).unwrap();
     /// ```
     pub fn send_transaction(&self, tx: Transaction) -> ConnectionResult<String> {
-        let client = reqwest::Client::new();
-
-        let mut client = client.post(&self.url);
+        let response = self.send_with_failover(&tx, |node, tx| self.send_to_node(node, tx))?;
 
-        let mut post_data = tx.get_data().to_string();
+        // Read outside of failover, the node has already accepted the transaction
+        self.read_body(response)
+    }
 
-        // Encrypt the data if needed
-        if self.encrypt {
-            let key_data = match &self.node_key_data {
-                Some(key_data) => key_data,
-                None => return Err(EncryptionError(4000)),
-            };
+    /// Send a transaction and measure how long the node took to respond
+    ///
+    /// Works like `send_transaction()`, also returning the time from sending the
+    /// transaction to reading the whole response. When a node can't be reached the
+    /// time includes trying the next node.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{Connection, Transaction};
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    ///
+    /// let transaction = Transaction::new("{Transaction Data}");
+    ///
+    /// let response = connection.send_transaction_timed(transaction).unwrap();
+    /// println!("Response received in {:?}", response.elapsed);
+    /// ```
+    ///
+    /// ## Errors
+    /// The same errors as `send_transaction()` are returned.
+    pub fn send_transaction_timed(&self, tx: Transaction) -> ConnectionResult<TimedResponse> {
+        let start = Instant::now();
 
-            post_data = Connection::encrypt(&key_data, &post_data)?;
+        let body = self.send_transaction(tx)?;
 
-            client = client.header("X-Activeledger-Encrypt", "1");
-        }
+        Ok(TimedResponse {
+            body,
+            elapsed: start.elapsed(),
+        })
+    }
 
-        // Post the transaction to the node
-        let mut response = match client.body(post_data).send() {
-            Ok(response) => response,
-            Err(_) => return Err(HttpError(1000)),
-        };
+    /// Send a transaction and return the response without reading its body
+    ///
+    /// The response implements `std::io::Read`, so large responses can be
+    /// read or deserialized incrementally instead of being held in a single String.
+    /// Otherwise this works like `send_transaction()`.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{Connection, Transaction};
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    ///
+    /// let transaction = Transaction::new("{Transaction Data}");
+    ///
+    /// let response = connection.send_transaction_streaming(transaction).unwrap();
+    /// let data: serde_json::Value = serde_json::from_reader(response).unwrap();
+    /// ```
+    pub fn send_transaction_streaming(&self, tx: Transaction) -> ConnectionResult<Response> {
+        self.send_with_failover(&tx, |node, tx| self.send_to_node(node, tx))
+    }
 
-        // If the status isn't 200 throw an error
-        if response.status().is_success() {
-            match response.text() {
-                Ok(body) => {
-                    println!("{:?}", body);
-                    Ok(body)
-                }
-                Err(_) => return Err(ResponseError(3000)),
-            }
-        } else {
-            Err(ResponseError(3001))
-        }
+    /// Send multiple transactions via this connection
+    ///
+    /// The transactions are sent one after another using the same HTTP client.
+    /// A result is returned for each transaction in the order they were given,
+    /// a failed transaction does not stop the remaining ones from being sent.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{Connection, Transaction};
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    ///
+    /// let transactions = vec![
+    ///     Transaction::new("{Transaction Data}"),
+    ///     Transaction::new("{Transaction Data}"),
+    /// ];
+    ///
+    /// let responses = connection.send_transactions(transactions);
+    /// ```
+    pub fn send_transactions(&self, txs: Vec<Transaction>) -> Vec<ConnectionResult<String>> {
+        txs.into_iter()
+            .map(|tx| self.send_transaction(tx))
+            .collect()
     }
-}
 
-// Private functions
+    /// Send a transaction and get the streams it created and updated
+    ///
+    /// Useful when onboarding, the id of the new identity is in `new`.
+    /// Both lists are empty if the transaction did not create or update any streams.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{Connection, Transaction};
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    ///
+    /// let transaction = Transaction::new("{Transaction Data}");
+    ///
+    /// let streams = connection.send_and_get_streams(transaction).unwrap();
+    ///
+    /// for stream in streams.new {
+    ///     println!("Created stream {}", stream.id);
+    /// }
+    /// ```
+    pub fn send_and_get_streams(&self, tx: Transaction) -> ConnectionResult<Streams> {
+        let body = self.send_transaction(tx)?;
 
-impl Connection {
-    /// Get the PEM from a Node and return it as a NodeKeyData struct
-    fn get_node_key_data(url: &str) -> ConnectionResult<NodeKeyData> {
-        let url = format!("{}/a/status", url);
-        let mut response = match reqwest::get(&url) {
-            Ok(val) => val,
-            Err(_) => return Err(EncryptionError(4001)),
-        };
+        let response = TransactionResponse::parse(&body)?;
 
-        // Check if response code is 200
-        if !response.status().is_success() {
-            return Err(ResponseError(3001));
-        }
+        Ok(response.streams)
+    }
 
-        let body = match response.text() {
-            Ok(body) => body,
-            Err(_) => return Err(EncryptionError(4002)),
-        };
+    /// Send a transaction and check that it was committed
+    ///
+    /// Parses the response and checks it with `TransactionResponse::check()`,
+    /// so contract rejections and commit failures are returned as errors.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{Connection, Transaction};
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    ///
+    /// let transaction = Transaction::new("{Transaction Data}");
+    ///
+    /// let response = connection.send_and_check(transaction).unwrap();
+    /// println!("Committed {}", response.umid);
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns ResponseError 3008 if the contract rejected the transaction, which
+    /// should not be retried, and 3009 if it was not committed, which can be.
+    pub fn send_and_check(&self, tx: Transaction) -> ConnectionResult<TransactionResponse> {
+        let body = self.send_transaction(tx)?;
 
-        let data_obj: serde_json::Value = match serde_json::from_str(&body) {
-            Ok(json) => json,
-            Err(_) => return Err(EncryptionError(4003)),
-        };
+        let response = TransactionResponse::parse(&body)?;
+        response.check()?;
 
-        let pem = match data_obj["pem"].as_str() {
-            Some(pem) => pem,
-            None => return Err(EncryptionError(4003)),
-        };
+        Ok(response)
+    }
 
-        Ok(NodeKeyData {
-            _encryption: String::from("rsa"),
-            pem: pem.to_string(),
-        })
+    /// Wrap a `$tx` object in a transaction, sign it and send it
+    ///
+    /// Creates the transaction from the unsigned `$tx` body, signs it with the
+    /// key under the given identity and sends it with `send_transaction()`.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{key::RSA, Connection};
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    /// let key = RSA::new("keyname").unwrap();
+    ///
+    /// let tx_body = serde_json::json!({
+    ///     "$namespace": "default",
+    ///     "$contract": "contract",
+    ///     "$i": { "identity": {} }
+    /// });
+    ///
+    /// let response = connection.sign_and_send(tx_body, "identity", &key).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns EncodingError 5002 if the transaction can't be created or signed,
+    /// otherwise the same errors as `send_transaction()`.
+    pub fn sign_and_send(
+        &self,
+        tx_body: serde_json::Value,
+        identity: &str,
+        key: &dyn Key,
+    ) -> ConnectionResult<String> {
+        self.send_signed(tx_body, identity, key, false)
     }
 
-    /// Encrypt the transaction
-    fn encrypt(node_key_data: &NodeKeyData, tx: &str) -> ConnectionResult<String> {
-        // Base64 decode the PEM
-        let pem = match decode(&node_key_data.pem) {
-            Ok(pem) => pem,
-            Err(err) => {
-                println!("{}", err);
-                return Err(EncryptionError(4004));
-            }
-        };
+    /// Wrap a `$tx` object in a self signed transaction, sign it and send it
+    ///
+    /// Works like `sign_and_send()` with `$selfsign` set, as used when onboarding
+    /// an identity that doesn't exist yet.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{key::{Key, RSA}, Connection};
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    /// let key = RSA::new("identity").unwrap();
+    ///
+    /// let tx_body = serde_json::json!({
+    ///     "$namespace": "default",
+    ///     "$contract": "onboard",
+    ///     "$i": {
+    ///         "identity": { "type": "rsa", "publicKey": key.get_pem().unwrap().public }
+    ///     }
+    /// });
+    ///
+    /// let response = connection.sign_and_send_selfsigned(tx_body, "identity", &key).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// The same errors as `sign_and_send()` are returned.
+    pub fn sign_and_send_selfsigned(
+        &self,
+        tx_body: serde_json::Value,
+        identity: &str,
+        key: &dyn Key,
+    ) -> ConnectionResult<String> {
+        self.send_signed(tx_body, identity, key, true)
+    }
 
-        // Create a new public key only
-        let key = match PKey::public_key_from_pem(&pem) {
-            Ok(key) => key,
-            Err(_) => return Err(EncryptionError(4005)),
-        };
+    /// Onboard a key and return the id of the new identity stream
+    ///
+    /// The onboard transaction is created and signed using `Transaction::onboard()`.
+    /// Works with any key type, pass an RSA or EllipticCurve key.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{key::RSA, Connection};
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    /// let key = RSA::new("identity").unwrap();
+    ///
+    /// let stream_id = connection.onboard(&key).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns EncodingError 5002 if the transaction can't be created and
+    /// ResponseError 3005 if the node didn't create a stream.
+    pub fn onboard(&self, key: &dyn Key) -> ConnectionResult<String> {
+        self.onboard_as(key.name(), key)
+    }
 
-        // Get the RSA version of the key
-        let rsa = match key.rsa() {
-            Ok(rsa) => rsa,
-            Err(_) => return Err(EncryptionError(4006)),
+    /// Onboard a key under an identity label and return the id of the new identity stream
+    ///
+    /// Works like `onboard()`, using `Transaction::onboard_as()` so the label is
+    /// used for both the `$i` input and the `$sigs` entry.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{key::RSA, Connection};
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    /// let key = RSA::new("keyname").unwrap();
+    ///
+    /// let stream_id = connection.onboard_as("identity", &key).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// The same errors as `onboard()` are returned.
+    pub fn onboard_as(&self, identity: &str, key: &dyn Key) -> ConnectionResult<String> {
+        let tx = match Transaction::onboard_as(identity, key) {
+            Ok(tx) => tx,
+            Err(error) => return Err(EncodingError(5002, Some(Arc::new(error)))),
         };
 
-        // Chunck the transaction to avoid data limits
-        let tx_chunks: Vec<&[u8]> = tx.as_bytes().chunks(100).collect();
-
-        // Initialise the end encrypted data holder as an empty string
-        let mut encrypted_data_holder = String::from("");
+        let streams = self.send_and_get_streams(tx)?;
 
-        // Encrypt the chunks and append them to the data holder
-        for chunk in tx_chunks {
-            let mut buffer = vec![0; rsa.size() as usize];
-            match rsa.public_encrypt(chunk, &mut buffer, Padding::PKCS1_OAEP) {
-                Ok(_) => (),
-                Err(_) => return Err(EncryptionError(4007)),
-            };
+        // Prefer the stream named after the identity
+        let stream = streams
+            .new
+            .iter()
+            .find(|stream| stream.name.as_deref() == Some(identity))
+            .or_else(|| streams.new.first());
 
-            // Activeledger splits on | so add that to the string between chunks
-            encrypted_data_holder = format!("{}{}|", &encrypted_data_holder, &encode(&buffer));
+        match stream {
+            Some(stream) => Ok(stream.id.clone()),
+            None => Err(ResponseError(3005, None)),
         }
+    }
+
+    /// Check if the active node's key has been fetched
+    ///
+    /// Connections that encrypt normally fetch the node keys when they are
+    /// created. Lazy connections, and nodes that couldn't be reached at the time,
+    /// fetch the key when the first transaction is sent to them. Always false when
+    /// the connection doesn't encrypt.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let connection = Connection::new_unchecked("http://localhost:5260", true).unwrap();
+    ///
+    /// assert!(!connection.has_node_key());
+    /// ```
+    pub fn has_node_key(&self) -> bool {
+        let node = &self.nodes[self.active_node.load(Ordering::Relaxed)];
+
+        self.encrypt && node.key_data().is_some()
+    }
+
+    /// Fetch the public keys of the nodes again
+    ///
+    /// The keys used for encryption are cached when the connection is created,
+    /// call this after a node's key has changed. Clones of the connection share
+    /// the refreshed keys. Does nothing if the connection doesn't encrypt.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let connection = Connection::new("http://localhost:5260", true).unwrap();
+    ///
+    /// connection.refresh_node_key().unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns the error from the last node if none of the keys could be fetched,
+    /// the cached keys are kept for nodes that fail.
+    pub fn refresh_node_key(&self) -> ConnectionResult<()> {
+        if !self.encrypt {
+            return Ok(());
+        }
+
+        let mut last_error = None;
+        let mut refreshed = false;
+
+        for node in self.nodes.iter() {
+            match Connection::get_node_key_data(&self.client, &node.status_url) {
+                Ok(key_data) => {
+                    node.set_key_data(key_data);
+                    refreshed = true;
+                }
+                Err(error) => {
+                    warn!("The key for {} could not be refreshed: {}", node.url, error);
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        match last_error {
+            Some(error) if !refreshed => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that a node can be reached
+    ///
+    /// Requests each node's status in turn, using the connection's HTTP client,
+    /// until one responds. The node that responds is used for the next transaction.
+    /// This is the same check made when the connection is created.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let connection = Connection::new("http://localhost:5260", false).unwrap();
+    ///
+    /// connection.check().unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns HttpError 1001 if none of the nodes respond.
+    pub fn check(&self) -> ConnectionResult<()> {
+        Connection::test_connection(self)
+    }
+
+    /// Check that a node can be reached, see `check()`
+    pub fn is_healthy(&self) -> bool {
+        self.check().is_ok()
+    }
+
+    /// Check whether a node provides a key for encrypting transactions
+    ///
+    /// Requests the node's status and checks that it includes a key, without
+    /// creating a connection. This allows falling back to unencrypted transactions
+    /// for nodes that don't support encryption instead of handling an error from
+    /// `Connection::new()`.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let url = "http://localhost:5260";
+    ///
+    /// let encrypt = Connection::supports_encryption(url).unwrap();
+    /// let connection = Connection::new(url, encrypt).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns EncryptionError 4001 if the node can't be reached, 4009 if it doesn't
+    /// respond in time, 4002 if the response can't be read and 4003 if it isn't JSON.
+    /// ResponseError 3001 is returned if the node responds with an error status.
+    pub fn supports_encryption(url: &str) -> ConnectionResult<bool> {
+        let client = Connection::default_client(ClientOptions::default())?;
+        let node = Node::new(url, DEFAULT_STATUS_PATH);
+
+        let (_, status) = Connection::get_status(&client, &node.status_url)?;
+
+        Ok(status["pem"].is_string())
+    }
+
+    /// Get the URL of the node the next transaction will be sent to
+    ///
+    /// With more than one node this changes when a node can't be reached.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let connection = Connection::new_unchecked("http://localhost:5260", false).unwrap();
+    ///
+    /// assert_eq!(connection.url(), "http://localhost:5260");
+    /// ```
+    pub fn url(&self) -> &str {
+        &self.nodes[self.active_node.load(Ordering::Relaxed)].url
+    }
+
+    /// Get the URLs of the nodes in the current node's neighbourhood
+    ///
+    /// Reads the `neighbourhood` list from the node's status, each entry gives
+    /// the `host` and `port` of a node. The URLs use the same scheme as the
+    /// current node and can be given to `Connection::new_pool()`. The list usually
+    /// includes the current node. A node that doesn't report a neighbourhood
+    /// returns an empty list, entries without a host or port are skipped.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let seed = Connection::new("http://localhost:5260", false).unwrap();
+    ///
+    /// let peers = seed.discover_peers().unwrap();
+    /// let urls: Vec<&str> = peers.iter().map(String::as_str).collect();
+    ///
+    /// let connection = Connection::new_pool(urls, false).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns HttpError 1001 if the node can't be reached, ResponseError 3001
+    /// if it doesn't return 200 and 3002 if the status is not valid JSON.
+    pub fn discover_peers(&self) -> ConnectionResult<Vec<String>> {
+        let node = &self.nodes[self.active_node.load(Ordering::Relaxed)];
 
-        // Return the data with the final | stripped off the end
-        return Ok(encrypted_data_holder[0..(encrypted_data_holder.len() - 1)].to_string());
+        let mut response = match self.client.get(&node.status_url).send() {
+            Ok(response) => response,
+            Err(error) => return Err(HttpError(1001, Some(Arc::new(error)))),
+        };
+
+        if !response.status().is_success() {
+            return Err(Connection::status_error(&node.status_url, &response));
+        }
+
+        let status: serde_json::Value = match response.json() {
+            Ok(status) => status,
+            Err(error) => return Err(ResponseError(3002, Some(Arc::new(error)))),
+        };
+
+        let neighbourhood = match status["neighbourhood"].as_array() {
+            Some(neighbourhood) => neighbourhood,
+            None => return Ok(Vec::new()),
+        };
+
+        let scheme = match url::Url::parse(&node.url) {
+            Ok(url) => url.scheme().to_string(),
+            Err(_) => String::from("http"),
+        };
+
+        let mut peers = Vec::new();
+
+        for neighbour in neighbourhood {
+            // Node configs give the port as a string or a number
+            let port = match &neighbour["port"] {
+                serde_json::Value::String(port) => port.clone(),
+                serde_json::Value::Number(port) => port.to_string(),
+                _ => continue,
+            };
+
+            let host = match neighbour["host"].as_str() {
+                Some(host) => host,
+                None => continue,
+            };
+
+            let peer = format!("{}://{}:{}", scheme, host, port);
+
+            if !peers.contains(&peer) {
+                peers.push(peer);
+            }
+        }
+
+        debug!("Discovered {} peers from {}", peers.len(), node.url);
+
+        Ok(peers)
     }
 
+    /// Whether transactions are encrypted before they are sent
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypt
+    }
+
+    /// Get the server sent events URL for changes to the given stream
+    ///
+    /// The URL can be used with the active_events crate to subscribe to the stream.
+    /// Activity subscriptions are served by Activecore, by default on port 5261
+    /// rather than the node's port 5260. Set the Activecore URL with the builder's
+    /// `events_url` option, otherwise the URL of the node in use is assumed.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Connection;
+    /// let connection = Connection::builder()
+    ///     .url("http://localhost:5260")
+    ///     .events_url("http://localhost:5261")
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let url = connection.subscribe_url("stream id");
+    /// ```
+    pub fn subscribe_url(&self, stream_id: &str) -> String {
+        let base = match &self.events_url {
+            Some(url) => url,
+            None => self.url(),
+        };
+
+        format!(
+            "{}/api/activity/subscribe/{}",
+            base.trim_end_matches('/'),
+            stream_id
+        )
+    }
+}
+
+// Private functions
+
+impl Connection {
+    /// Create the HTTP client used when one isn't given
+    pub(super) fn default_client(options: ClientOptions) -> ConnectionResult<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(proxy) = options.proxy {
+            match reqwest::Proxy::all(proxy) {
+                Ok(proxy) => builder = builder.proxy(proxy),
+                Err(error) => return Err(UrlError(2002, Some(Arc::new(error)))),
+            }
+        }
+
+        builder = builder
+            .use_sys_proxy()
+            .redirect(reqwest::RedirectPolicy::none());
+
+        if let Some(timeout) = options.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        if let Some(timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        if let Some(max) = options.max_idle {
+            builder = builder.max_idle_per_host(max);
+        }
+
+        // Certificate presented to nodes that require mutual TLS
+        if let Some((der, passphrase)) = options.identity {
+            match reqwest::Identity::from_pkcs12_der(der, passphrase) {
+                Ok(identity) => builder = builder.identity(identity),
+                Err(error) => return Err(HttpError(1004, Some(Arc::new(error)))),
+            }
+        }
+
+        // The client is shared by all requests made by this connection
+        match builder.build() {
+            Ok(client) => Ok(client),
+            Err(error) => Err(HttpError(1002, Some(Arc::new(error)))),
+        }
+    }
+
+    /// Create the connection, get the node keys if needed and test it
+    pub(super) fn create(
+        urls: Vec<&str>,
+        encrypt: bool,
+        client: reqwest::Client,
+        status_path: &str,
+        lazy: bool,
+    ) -> ConnectionResult<Connection> {
+        if urls.is_empty() {
+            return Err(UrlError(2001, None));
+        }
+
+        let mut nodes = Vec::with_capacity(urls.len());
+        let mut key_error = None;
+
+        for url in urls {
+            let node = Node::new(url, status_path);
+
+            // If encrypt is true we should get the key data now instead of each tx run
+            if encrypt && !lazy {
+                match Connection::get_node_key_data(&client, &node.status_url) {
+                    Ok(data) => node.set_key_data(data),
+                    Err(error) => key_error = key_error.or(Some(error)),
+                }
+            }
+
+            nodes.push(node);
+        }
+
+        // None of the nodes provided their key
+        if encrypt && !lazy && nodes.iter().all(|node| node.key_data().is_none()) {
+            if let Some(error) = key_error {
+                return Err(error);
+            }
+        }
+
+        let connection = Connection {
+            nodes: Arc::new(nodes),
+            active_node: AtomicUsize::new(0),
+            encrypt,
+            gzip: false,
+            oaep_hash: OaepHash::default(),
+            events_url: None,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            encrypt_header: Connection::default_encrypt_header(),
+            client,
+        };
+
+        // If connection test successful return Ok
+        if !lazy {
+            Connection::test_connection(&connection)?;
+        }
+
+        Ok(connection)
+    }
+
+    /// Get the PEM from a Node's status URL and return it as a NodeKeyData struct
+    fn get_node_key_data(client: &reqwest::Client, url: &str) -> ConnectionResult<NodeKeyData> {
+        debug!("Fetching the node key from {}", url);
+
+        let (body, data_obj) = Connection::get_status(client, url)?;
+
+        let pem = match data_obj["pem"].as_str() {
+            Some(pem) => pem,
+            None => {
+                let source = UnexpectedResponse::new(&body);
+                return Err(EncryptionError(4003, Some(Arc::new(source))));
+            }
+        };
+
+        info!("Fetched the node key from {}", url);
+
+        Ok(NodeKeyData {
+            _encryption: String::from("rsa"),
+            pem: pem.to_string(),
+        })
+    }
+
+    /// Fetch a node key that wasn't cached, retrying once if the node can't be reached
+    ///
+    /// The key is cached so later transactions don't fetch it again.
+    fn fetch_node_key(&self, node: &Node) -> ConnectionResult<NodeKeyData> {
+        let mut attempt = 1;
+
+        loop {
+            match Connection::get_node_key_data(&self.client, &node.status_url) {
+                Ok(key_data) => {
+                    node.set_key_data(key_data.clone());
+                    return Ok(key_data);
+                }
+                Err(error)
+                    if attempt < NODE_KEY_ATTEMPTS && Connection::is_transport_error(&error) =>
+                {
+                    warn!(
+                        "The key for {} could not be fetched, retrying: {}",
+                        node.url, error
+                    );
+                    attempt += 1;
+                    thread::sleep(NODE_KEY_RETRY_DELAY);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    /// Get the body of a node's status response and the JSON it contains
+    fn get_status(
+        client: &reqwest::Client,
+        url: &str,
+    ) -> ConnectionResult<(String, serde_json::Value)> {
+        let mut response = match client.get(url).send() {
+            Ok(val) => val,
+            Err(error) if error.is_timeout() => {
+                return Err(EncryptionError(4009, Some(Arc::new(error))))
+            }
+            Err(error) => return Err(EncryptionError(4001, Some(Arc::new(error)))),
+        };
+
+        // Check if response code is 200
+        if !response.status().is_success() {
+            return Err(Connection::status_error(url, &response));
+        }
+
+        let body = match response.text() {
+            Ok(body) => body,
+            Err(error) if error.is_timeout() => {
+                return Err(EncryptionError(4009, Some(Arc::new(error))))
+            }
+            Err(error) => return Err(EncryptionError(4002, Some(Arc::new(error)))),
+        };
+
+        let data_obj: serde_json::Value = match serde_json::from_str(&body) {
+            Ok(json) => json,
+            Err(error) => return Err(EncryptionError(4003, Some(Arc::new(error)))),
+        };
+
+        Ok((body, data_obj))
+    }
+
+    /// Create the transaction envelope for a `$tx` body, sign it and send it
+    fn send_signed(
+        &self,
+        tx_body: serde_json::Value,
+        identity: &str,
+        key: &dyn Key,
+        selfsign: bool,
+    ) -> ConnectionResult<String> {
+        let mut data = serde_json::json!({ "$tx": tx_body, "$sigs": {} });
+
+        if selfsign {
+            data["$selfsign"] = true.into();
+        }
+
+        let mut tx = Transaction::from_value(data);
+
+        if let Err(error) = tx.sign(identity, key) {
+            return Err(EncodingError(5002, Some(Arc::new(error))));
+        }
+
+        self.send_transaction(tx)
+    }
+
+    /// Send a transaction to each node in turn until one can be reached
+    ///
+    /// Nodes are tried starting with the last one that could be reached.
+    fn send_with_failover<T, F>(&self, tx: &Transaction, send: F) -> ConnectionResult<T>
+    where
+        F: Fn(&Node, &Transaction) -> ConnectionResult<T>,
+    {
+        let start = self.active_node.load(Ordering::Relaxed);
+        let mut last_error = None;
+
+        for index in Connection::node_order(start, self.nodes.len()) {
+            match send(&self.nodes[index], tx) {
+                Err(error) if Connection::is_transport_error(&error) => {
+                    warn!(
+                        "Node {} could not be reached: {}",
+                        self.nodes[index].url, error
+                    );
+                    last_error = Some(error);
+                }
+                result => {
+                    self.active_node.store(index, Ordering::Relaxed);
+                    return result;
+                }
+            }
+        }
+
+        match last_error {
+            Some(error) => Err(error),
+            None => Err(UrlError(2001, None)),
+        }
+    }
+
+    /// Send a transaction to a single node and check the response status
+    fn send_to_node(&self, node: &Node, tx: &Transaction) -> ConnectionResult<Response> {
+        let request = self.prepare_request(node, tx)?;
+
+        debug!("Sending transaction to {}", node.url);
+
+        // Post the transaction to the node
+        let response = match self.client.execute(request) {
+            Ok(response) => response,
+            Err(error) => return Err(Connection::send_error(error)),
+        };
+
+        // If the status isn't 200 throw an error
+        if response.status().is_success() {
+            debug!("Transaction sent to {}", node.url);
+
+            Ok(response)
+        } else {
+            Err(Connection::status_error(&node.url, &response))
+        }
+    }
+
+    /// Read a response body, failing if it is larger than the maximum response size
+    fn read_body(&self, response: Response) -> ConnectionResult<String> {
+        let limit = self.max_response_size;
+
+        // Don't start reading a body that is known to be too large
+        if response.content_length().unwrap_or(0) > limit {
+            return Err(ResponseError(3007, None));
+        }
+
+        // The length header may be missing or wrong, so limit what is read as well
+        let mut body = Vec::new();
+        if let Err(error) = response
+            .take(limit.saturating_add(1))
+            .read_to_end(&mut body)
+        {
+            if error.kind() == std::io::ErrorKind::TimedOut {
+                return Err(HttpError(1006, Some(Arc::new(error))));
+            }
+            return Err(ResponseError(3000, Some(Arc::new(error))));
+        }
+
+        if body.len() as u64 > limit {
+            return Err(ResponseError(3007, None));
+        }
+
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// The header that tells the node the transaction is encrypted
+    fn default_encrypt_header() -> (HeaderName, HeaderValue) {
+        let (name, value) = DEFAULT_ENCRYPT_HEADER;
+        (
+            HeaderName::from_static(name),
+            HeaderValue::from_static(value),
+        )
+    }
+
+    /// The error for a request that failed, separating errors that happened before
+    /// the node received anything from those that happened after
+    ///
+    /// Connect failures are HttpError 1001 and connect timeouts 1005. The request
+    /// timeout is 1006 and any other error is 1000, the node may have received
+    /// the transaction in both cases.
+    fn send_error(error: reqwest::Error) -> ConnectionError {
+        let connect = error
+            .get_ref()
+            .and_then(|source| source.downcast_ref::<hyper::Error>())
+            .is_some_and(hyper::Error::is_connect);
+
+        let code = match (connect, error.is_timeout()) {
+            (true, true) => 1005,
+            (true, false) => 1001,
+            (false, true) => 1006,
+            (false, false) => 1000,
+        };
+
+        HttpError(code, Some(Arc::new(error)))
+    }
+
+    /// The error for a response that isn't successful, redirects include their location
+    fn status_error(url: &str, response: &Response) -> ConnectionError {
+        let status = response.status();
+        warn!("{} returned {}", url, status);
+
+        if status.is_redirection() {
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok());
+
+            let redirect = Redirect::new(status.as_u16(), location);
+            return ResponseError(3006, Some(Arc::new(redirect)));
+        }
+
+        ResponseError(3001, None)
+    }
+
+    /// Create the request used to post a transaction to a node
+    fn prepare_request(&self, node: &Node, tx: &Transaction) -> ConnectionResult<reqwest::Request> {
+        let mut client = self.client.post(&node.url);
+
+        if self.encrypt {
+            let (name, value) = &self.encrypt_header;
+            client = client.header(name.clone(), value.clone());
+        }
+
+        if self.gzip {
+            client = client.header("Content-Encoding", "gzip");
+        }
+
+        let body = self.prepare_body(node, tx)?;
+
+        match client.body(body).build() {
+            Ok(request) => Ok(request),
+            Err(error) => Err(HttpError(1000, Some(Arc::new(error)))),
+        }
+    }
+
+    /// Encrypt and compress the transaction data as needed
+    ///
+    /// Compression is applied to the encrypted data, the node removes
+    /// the Content-Encoding before decrypting the transaction.
+    fn prepare_body(&self, node: &Node, tx: &Transaction) -> ConnectionResult<Vec<u8>> {
+        let mut post_data = tx.get_data().to_string();
+
+        // Encrypt the data if needed
+        if self.encrypt {
+            let cached = node
+                .key_data()
+                .as_ref()
+                .map(|key_data| Connection::encrypt(key_data, &post_data, self.oaep_hash));
+
+            post_data = match cached {
+                Some(encrypted) => encrypted?,
+                None => {
+                    // The node couldn't be reached when the connection was created
+                    let key_data = self.fetch_node_key(node)?;
+                    Connection::encrypt(&key_data, &post_data, self.oaep_hash)?
+                }
+            };
+        }
+
+        if self.gzip {
+            return Connection::compress(post_data.as_bytes());
+        }
+
+        Ok(post_data.into_bytes())
+    }
+
+    /// Gzip the given data
+    fn compress(data: &[u8]) -> ConnectionResult<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+
+        if let Err(error) = encoder.write_all(data) {
+            return Err(EncodingError(5001, Some(Arc::new(error))));
+        }
+
+        match encoder.finish() {
+            Ok(compressed) => Ok(compressed),
+            Err(error) => Err(EncodingError(5001, Some(Arc::new(error)))),
+        }
+    }
+
+    /// The order to try the nodes in, starting at the given node and wrapping around
+    fn node_order(start: usize, count: usize) -> impl Iterator<Item = usize> {
+        (0..count).map(move |offset| (start + offset) % count)
+    }
+
+    /// Whether an error means the node couldn't be reached
+    ///
+    /// Only errors where the node can't have received the transaction count,
+    /// trying another node after a timeout or reset could submit it twice.
+    /// Fetching the node key is safe to repeat.
+    fn is_transport_error(error: &ConnectionError) -> bool {
+        matches!(
+            error,
+            HttpError(1001, _)
+                | HttpError(1005, _)
+                | EncryptionError(4001, _)
+                | EncryptionError(4009, _)
+        )
+    }
+
+    /// Encrypt the transaction
+    fn encrypt(
+        node_key_data: &NodeKeyData,
+        tx: &str,
+        oaep_hash: OaepHash,
+    ) -> ConnectionResult<String> {
+        // There are no chunks to encrypt
+        if tx.is_empty() {
+            return Err(EncryptionError(4008, None));
+        }
+
+        // Base64 decode the PEM
+        let pem = match decode(&node_key_data.pem) {
+            Ok(pem) => pem,
+            Err(error) => return Err(EncryptionError(4004, Some(Arc::new(error)))),
+        };
+
+        // Create a new public key only
+        let key = match PKey::public_key_from_pem(&pem) {
+            Ok(key) => key,
+            Err(error) => return Err(EncryptionError(4005, Some(Arc::new(error)))),
+        };
+
+        // Get the RSA version of the key
+        let rsa = match key.rsa() {
+            Ok(rsa) => rsa,
+            Err(error) => return Err(EncryptionError(4006, Some(Arc::new(error)))),
+        };
+
+        Connection::encrypt_chunks(&key, rsa.size() as usize, tx.as_bytes(), oaep_hash)
+    }
+
+    /// Create an encrypter for the node key
+    fn encrypter(key: &PKey<Public>, oaep_hash: OaepHash) -> ConnectionResult<Encrypter<'_>> {
+        // Set the OAEP and MGF1 digests explicitly rather than relying on the defaults
+        Encrypter::new(key)
+            .and_then(|mut encrypter| {
+                encrypter.set_rsa_padding(Padding::PKCS1_OAEP)?;
+                encrypter.set_rsa_oaep_md(oaep_hash.digest())?;
+                encrypter.set_rsa_mgf1_md(oaep_hash.digest())?;
+                Ok(encrypter)
+            })
+            .map_err(|error| EncryptionError(4007, Some(Arc::new(error))))
+    }
+
+    /// Encrypt the transaction in chunks, joining the base64 encoded blocks with a |
+    ///
+    /// With the `rayon` feature the chunks are encrypted in parallel.
+    #[cfg(not(feature = "rayon"))]
+    fn encrypt_chunks(
+        key: &PKey<Public>,
+        block_size: usize,
+        tx: &[u8],
+        oaep_hash: OaepHash,
+    ) -> ConnectionResult<String> {
+        use base64::{encode_config_buf, STANDARD};
+
+        let encrypter = Connection::encrypter(key, oaep_hash)?;
+
+        // Chunck the transaction to avoid data limits
+        let tx_chunks = tx.chunks(ENCRYPT_CHUNK_SIZE);
+
+        // Each chunk becomes one base64 encoded RSA block followed by a |
+        let encoded_size = block_size.div_ceil(3) * 4;
+        let mut encrypted_data = String::with_capacity(tx_chunks.len() * (encoded_size + 1));
+
+        let mut buffer = vec![0; block_size];
+
+        // Encrypt the chunks and append them to the encrypted data
+        for chunk in tx_chunks {
+            match encrypter.encrypt(chunk, &mut buffer) {
+                Ok(_) => (),
+                Err(error) => return Err(EncryptionError(4007, Some(Arc::new(error)))),
+            };
+
+            // Activeledger splits on | so add that to the string between chunks
+            if !encrypted_data.is_empty() {
+                encrypted_data.push('|');
+            }
+
+            encode_config_buf(&buffer, STANDARD, &mut encrypted_data);
+        }
+
+        Ok(encrypted_data)
+    }
+
+    /// Encrypt the transaction in chunks, joining the base64 encoded blocks with a |
+    ///
+    /// With the `rayon` feature the chunks are encrypted in parallel.
+    #[cfg(feature = "rayon")]
+    fn encrypt_chunks(
+        key: &PKey<Public>,
+        block_size: usize,
+        tx: &[u8],
+        oaep_hash: OaepHash,
+    ) -> ConnectionResult<String> {
+        use rayon::prelude::*;
+
+        // Encrypters can't be shared between threads so each thread creates its own
+        let blocks = tx
+            .par_chunks(ENCRYPT_CHUNK_SIZE)
+            .map_init(
+                || Connection::encrypter(key, oaep_hash),
+                |encrypter, chunk| {
+                    let encrypter = encrypter.as_ref().map_err(Clone::clone)?;
+                    let mut buffer = vec![0; block_size];
+
+                    match encrypter.encrypt(chunk, &mut buffer) {
+                        Ok(_) => Ok(base64::encode(&buffer)),
+                        Err(error) => Err(EncryptionError(4007, Some(Arc::new(error)))),
+                    }
+                },
+            )
+            .collect::<ConnectionResult<Vec<String>>>()?;
+
+        // Activeledger splits on | so add that to the string between chunks
+        Ok(blocks.join("|"))
+    }
+
+    /// Check that at least one of the nodes responds
     fn test_connection(connection: &Connection) -> ConnectionResult<()> {
-        let url = format!("{}/a/status", connection.url);
+        let mut last_error = None;
 
-        match reqwest::get(&url) {
-            Ok(_) => Ok(()),
-            Err(_) => return Err(HttpError(1001)),
+        for (index, node) in connection.nodes.iter().enumerate() {
+            match connection.client.get(&node.status_url).send() {
+                Ok(_) => {
+                    debug!("Connected to {}", node.url);
+                    connection.active_node.store(index, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(error) => {
+                    warn!("Node {} could not be reached: {}", node.url, error);
+                    last_error = Some(error);
+                }
+            }
         }
+
+        Err(HttpError(
+            1001,
+            last_error.map(|error| Arc::new(error) as ErrorSource),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    fn offline_connection(gzip: bool) -> Connection {
+        let mut connection = Connection::new_unchecked("http://localhost:5260", false).unwrap();
+        connection.gzip = gzip;
+
+        connection
+    }
+
+    #[test]
+    fn node_order_wraps() {
+        let order: Vec<usize> = Connection::node_order(1, 3).collect();
+        assert_eq!(order, vec![1, 2, 0]);
+
+        let order: Vec<usize> = Connection::node_order(0, 1).collect();
+        assert_eq!(order, vec![0]);
+    }
+
+    #[test]
+    fn node_status_url() {
+        let node = Node::new("http://localhost:5260", DEFAULT_STATUS_PATH);
+        assert_eq!(node.status_url, "http://localhost:5260/a/status");
+
+        let node = Node::new("http://gateway/", "/ledger/a/status");
+        assert_eq!(node.status_url, "http://gateway/ledger/a/status");
+
+        let node = Node::new("http://gateway", "ledger/a/status");
+        assert_eq!(node.status_url, "http://gateway/ledger/a/status");
+    }
+
+    #[test]
+    fn connection_debug() {
+        let connection = Connection::new_unchecked("http://localhost:5260", true).unwrap();
+        connection.nodes[0].set_key_data(NodeKeyData {
+            _encryption: String::from("rsa"),
+            pem: String::from("node key"),
+        });
+
+        assert_eq!(connection.url(), "http://localhost:5260");
+        assert!(connection.is_encrypted());
+
+        let debug = format!("{:?}", connection);
+        assert!(debug.contains("http://localhost:5260"));
+        assert!(debug.contains("encrypt: true"));
+        assert!(!debug.contains("node key"));
+    }
+
+    #[test]
+    fn client_identity() {
+        let der = std::fs::read("./testfiles/client.p12").unwrap();
+
+        let options = |identity| ClientOptions {
+            identity: Some(identity),
+            ..ClientOptions::default()
+        };
+
+        assert!(Connection::default_client(options((&der, "passphrase"))).is_ok());
+
+        let result = Connection::default_client(options((&der, "wrong")));
+        assert!(matches!(result, Err(HttpError(1004, Some(_)))));
+
+        let result = Connection::default_client(options((b"not a bundle", "")));
+        assert!(matches!(result, Err(HttpError(1004, Some(_)))));
+    }
+
+    #[test]
+    fn subscribe_url() {
+        let mut connection = offline_connection(false);
+        assert_eq!(
+            connection.subscribe_url("abc"),
+            "http://localhost:5260/api/activity/subscribe/abc"
+        );
+
+        connection.events_url = Some("http://localhost:5261/".to_string());
+        assert_eq!(
+            connection.subscribe_url("abc"),
+            "http://localhost:5261/api/activity/subscribe/abc"
+        );
+    }
+
+    #[test]
+    fn gzip_request() {
+        let connection = offline_connection(true);
+        let node = &connection.nodes[0];
+        let tx = Transaction::new(r#"{"$tx": {"$namespace": "default"}, "$sigs": {}}"#);
+
+        let request = connection.prepare_request(node, &tx).unwrap();
+        assert_eq!(request.headers()["Content-Encoding"], "gzip");
+
+        let body = connection.prepare_body(node, &tx).unwrap();
+
+        let mut decompressed = String::new();
+        GzDecoder::new(&body[..])
+            .read_to_string(&mut decompressed)
+            .unwrap();
+
+        assert_eq!(decompressed, tx.get_data());
+    }
+
+    #[test]
+    fn uncompressed_request() {
+        let connection = offline_connection(false);
+        let node = &connection.nodes[0];
+        let tx = Transaction::new(r#"{"$tx": {}}"#);
+
+        let request = connection.prepare_request(node, &tx).unwrap();
+        assert!(request.headers().get("Content-Encoding").is_none());
+
+        let body = connection.prepare_body(node, &tx).unwrap();
+        assert_eq!(body, tx.get_data().as_bytes());
+    }
+
+    fn node_key_data(key: &openssl::rsa::Rsa<openssl::pkey::Private>) -> NodeKeyData {
+        let public_pem = key.public_key_to_pem().unwrap();
+
+        NodeKeyData {
+            _encryption: String::from("rsa"),
+            pem: base64::encode(&public_pem),
+        }
+    }
+
+    #[test]
+    fn encrypt_chunks() {
+        let key = openssl::rsa::Rsa::generate(2048).unwrap();
+        let node_key_data = node_key_data(&key);
+
+        let tx = "x".repeat(250);
+        let encrypted = Connection::encrypt(&node_key_data, &tx, OaepHash::Sha1).unwrap();
+
+        let blocks: Vec<&str> = encrypted.split('|').collect();
+        assert_eq!(blocks.len(), 3);
+
+        let mut decrypted = Vec::new();
+
+        for block in blocks {
+            let block = base64::decode(block).unwrap();
+            let mut buffer = vec![0; key.size() as usize];
+
+            let len = key
+                .private_decrypt(&block, &mut buffer, Padding::PKCS1_OAEP)
+                .unwrap();
+
+            decrypted.extend_from_slice(&buffer[..len]);
+        }
+
+        assert_eq!(decrypted, tx.as_bytes());
+    }
+
+    #[test]
+    fn encrypt_empty() {
+        let key = openssl::rsa::Rsa::generate(2048).unwrap();
+
+        let result = Connection::encrypt(&node_key_data(&key), "", OaepHash::Sha1);
+        assert_eq!(result, Err(EncryptionError(4008, None)));
+    }
+
+    #[test]
+    fn encrypt_oaep_sha256() {
+        let key = openssl::rsa::Rsa::generate(2048).unwrap();
+        let pkey = PKey::from_rsa(key.clone()).unwrap();
+
+        let encrypted =
+            Connection::encrypt(&node_key_data(&key), "Test data", OaepHash::Sha256).unwrap();
+        let block = base64::decode(&encrypted).unwrap();
+
+        let mut decrypter = openssl::encrypt::Decrypter::new(&pkey).unwrap();
+        decrypter.set_rsa_padding(Padding::PKCS1_OAEP).unwrap();
+        decrypter.set_rsa_oaep_md(MessageDigest::sha256()).unwrap();
+        decrypter.set_rsa_mgf1_md(MessageDigest::sha256()).unwrap();
+
+        let mut buffer = vec![0; decrypter.decrypt_len(&block).unwrap()];
+        let len = decrypter.decrypt(&block, &mut buffer).unwrap();
+        assert_eq!(&buffer[..len], b"Test data");
+
+        // SHA-1 decryption of a SHA-256 block fails
+        let mut buffer = vec![0; key.size() as usize];
+        assert!(key
+            .private_decrypt(&block, &mut buffer, Padding::PKCS1_OAEP)
+            .is_err());
     }
 }
@@ -25,40 +25,52 @@
 
 use std::error::Error;
 use std::fmt;
+use std::mem;
+use std::sync::Arc;
+
+use crate::key::KeyError;
 
 /// ConnectionResult definition - Shorthand for: Result<T, ConnectionError>
 pub type ConnectionResult<T> = Result<T, ConnectionError>;
 
+/// The underlying error that caused a ConnectionError, if there was one
+pub type ErrorSource = Arc<dyn Error + Send + Sync>;
+
 /// ConnectionError data holder
-#[derive(Debug)]
+///
+/// Each variant holds the error code and, where available, the error that caused it.
+/// The cause can be retrieved using `Error::source()`.
+///
+/// Errors are compared by variant and code only, the source is ignored.
+#[derive(Debug, Clone)]
 pub enum ConnectionError {
-    HttpError(u16),       // 1000
-    UrlError(u16),        // 2000
-    ResponseError(u16),   // 3000
-    EncryptionError(u16), // 4000
-    EncodingError(u16),   // 5000
+    HttpError(u16, Option<ErrorSource>),       // 1000
+    UrlError(u16, Option<ErrorSource>),        // 2000
+    ResponseError(u16, Option<ErrorSource>),   // 3000
+    EncryptionError(u16, Option<ErrorSource>), // 4000
+    EncodingError(u16, Option<ErrorSource>),   // 5000
 }
 
 impl fmt::Display for ConnectionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ConnectionError::HttpError(ref code) => {
+            ConnectionError::HttpError(ref code, _) => {
                 let error = ConnectionErrorHandler::get_http_error(code);
                 write!(f, "HTTP Error - {}: {}", code, error)
             }
-            ConnectionError::UrlError(ref code) => {
+            ConnectionError::UrlError(ref code, _) => {
                 let error = ConnectionErrorHandler::get_url_error(code);
                 write!(f, "Url Error - {}: {}", code, error)
             }
-            ConnectionError::ResponseError(ref code) => {
+            ConnectionError::ResponseError(ref code, _) => {
                 let error = ConnectionErrorHandler::get_response_error(code);
                 write!(f, "Response Error - {}: {}", code, error)
             }
-            ConnectionError::EncryptionError(ref code) => {
+            ConnectionError::EncryptionError(ref code, _) => {
                 let error = ConnectionErrorHandler::get_encryption_error(code);
                 write!(f, "EncryptionError Error - {}: {}", code, error)
             }
-            ConnectionError::EncodingError(ref code) => {
+            ConnectionError::EncodingError(ref code, _) => {
                 let error = ConnectionErrorHandler::get_encoding_error(code);
                 write!(f, "Encoding Error - {}: {}", code, error)
             }
@@ -66,7 +78,264 @@ impl fmt::Display for ConnectionError {
     }
 }
 
-impl Error for ConnectionError {}
+impl ConnectionError {
+    fn code(&self) -> u16 {
+        match *self {
+            ConnectionError::HttpError(code, _) => code,
+            ConnectionError::UrlError(code, _) => code,
+            ConnectionError::ResponseError(code, _) => code,
+            ConnectionError::EncryptionError(code, _) => code,
+            ConnectionError::EncodingError(code, _) => code,
+        }
+    }
+}
+
+impl PartialEq for ConnectionError {
+    fn eq(&self, other: &Self) -> bool {
+        mem::discriminant(self) == mem::discriminant(other) && self.code() == other.code()
+    }
+}
+
+impl Eq for ConnectionError {}
+
+/// HTTP errors without a more specific code become HttpError 1003
+#[cfg(feature = "network")]
+impl From<reqwest::Error> for ConnectionError {
+    fn from(error: reqwest::Error) -> ConnectionError {
+        ConnectionError::HttpError(1003, Some(Arc::new(error)))
+    }
+}
+
+/// JSON errors are from parsing a response and become ResponseError 3002
+impl From<serde_json::Error> for ConnectionError {
+    fn from(error: serde_json::Error) -> ConnectionError {
+        ConnectionError::ResponseError(3002, Some(Arc::new(error)))
+    }
+}
+
+impl Error for ConnectionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        let source = match *self {
+            ConnectionError::HttpError(_, ref source) => source,
+            ConnectionError::UrlError(_, ref source) => source,
+            ConnectionError::ResponseError(_, ref source) => source,
+            ConnectionError::EncryptionError(_, ref source) => source,
+            ConnectionError::EncodingError(_, ref source) => source,
+        };
+
+        match source {
+            Some(source) => Some(source.as_ref()),
+            None => None,
+        }
+    }
+}
+
+/// TransactionResult definition - Shorthand for: Result<T, TransactionError>
+pub type TransactionResult<T> = Result<T, TransactionError>;
+
+/// TransactionError data holder
+///
+/// Like ConnectionError, the source is ignored when comparing errors.
+#[derive(Debug, Clone)]
+pub enum TransactionError {
+    DataError(u16, Option<ErrorSource>), // 1000
+    SigningError(KeyError),
+}
+
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransactionError::DataError(ref code, _) => {
+                let error = TransactionErrorHandler::get_data_error(code);
+                write!(f, "Data Error - {}: {}", code, error)
+            }
+            TransactionError::SigningError(ref error) => write!(f, "Signing Error - {}", error),
+        }
+    }
+}
+
+impl PartialEq for TransactionError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (TransactionError::DataError(code, _), TransactionError::DataError(other, _)) => {
+                code == other
+            }
+            (TransactionError::SigningError(error), TransactionError::SigningError(other)) => {
+                error == other
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for TransactionError {}
+
+impl Error for TransactionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            TransactionError::DataError(_, Some(ref source)) => Some(source.as_ref()),
+            TransactionError::DataError(_, None) => None,
+            TransactionError::SigningError(ref error) => Some(error),
+        }
+    }
+}
+
+/// A response from a node that could not be used
+///
+/// Used as the source of a ConnectionError when the response body helps to
+/// diagnose the problem. Long bodies are truncated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnexpectedResponse {
+    body: String,
+}
+
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+impl UnexpectedResponse {
+    /// The maximum number of bytes of the body that are kept
+    const MAX_LENGTH: usize = 256;
+
+    pub(crate) fn new(body: &str) -> UnexpectedResponse {
+        if body.len() <= UnexpectedResponse::MAX_LENGTH {
+            return UnexpectedResponse {
+                body: body.to_string(),
+            };
+        }
+
+        let mut end = UnexpectedResponse::MAX_LENGTH;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        UnexpectedResponse {
+            body: format!("{}...", &body[..end]),
+        }
+    }
+
+    /// Get the, possibly truncated, response body
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+}
+
+impl fmt::Display for UnexpectedResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unexpected response: {}", self.body)
+    }
+}
+
+impl Error for UnexpectedResponse {}
+
+/// A redirect returned by a node
+///
+/// Used as the source of ResponseError 3006. Redirects are not followed because
+/// the transaction would be lost when the POST is turned into a GET, so the
+/// node URL should be changed to the redirect's location instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    status: u16,
+    location: Option<String>,
+}
+
+#[cfg_attr(not(feature = "network"), allow(dead_code))]
+impl Redirect {
+    pub(crate) fn new(status: u16, location: Option<&str>) -> Redirect {
+        Redirect {
+            status,
+            location: location.map(String::from),
+        }
+    }
+
+    /// Get the HTTP status code of the redirect
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Get the `Location` header of the redirect, None if it was missing
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+}
+
+impl fmt::Display for Redirect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "Redirected ({}) to {}", self.status, location),
+            None => write!(f, "Redirected ({}) without a location", self.status),
+        }
+    }
+}
+
+impl Error for Redirect {}
+
+/// The errors a node listed in the `$summary` of a response
+///
+/// Used as the source of ResponseError 3008, 3009 and 3010.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeErrors {
+    errors: Vec<String>,
+}
+
+impl NodeErrors {
+    pub(crate) fn new(errors: &[String]) -> NodeErrors {
+        NodeErrors {
+            errors: errors.to_vec(),
+        }
+    }
+
+    /// Get the errors returned by the node
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+}
+
+impl fmt::Display for NodeErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Node errors: {}", self.errors.join("; "))
+    }
+}
+
+impl Error for NodeErrors {}
+
+/// The identities whose inputs and signatures don't match
+///
+/// Used as the source of DataError 1007.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureMismatch {
+    unsigned: Vec<String>,
+    unexpected: Vec<String>,
+}
+
+impl SignatureMismatch {
+    pub(crate) fn new(unsigned: Vec<String>, unexpected: Vec<String>) -> SignatureMismatch {
+        SignatureMismatch {
+            unsigned,
+            unexpected,
+        }
+    }
+
+    /// Get the inputs in `$i` that don't have a signature
+    pub fn unsigned(&self) -> &[String] {
+        &self.unsigned
+    }
+
+    /// Get the signatures in `$sigs` that don't have an input
+    pub fn unexpected(&self) -> &[String] {
+        &self.unexpected
+    }
+}
+
+impl fmt::Display for SignatureMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unsigned inputs: [{}], signatures without an input: [{}]",
+            self.unsigned.join(", "),
+            self.unexpected.join(", ")
+        )
+    }
+}
+
+impl Error for SignatureMismatch {}
 
 struct ConnectionErrorHandler;
 
@@ -74,7 +343,13 @@ impl ConnectionErrorHandler {
     fn get_http_error(code: &u16) -> &str {
         match code {
             1000 => "Error POSTing the transaction",
-            1001 => "Error during GET request",
+            1001 => "The node could not be reached",
+            1002 => "Error creating the HTTP client",
+            1003 => "Error making the HTTP request",
+            1004 => "Error reading the TLS client identity",
+            1005 => "Timed out connecting to the node",
+            1006 => "Timed out waiting for the node to respond",
+            1007 => "Invalid encryption header",
             _ => "Unknown Error",
         }
     }
@@ -82,6 +357,8 @@ impl ConnectionErrorHandler {
     fn get_url_error(code: &u16) -> &str {
         match code {
             2000 => "Error creating signer",
+            2001 => "No node URLs were given",
+            2002 => "Invalid proxy URL",
             _ => "Unknown Error",
         }
     }
@@ -90,6 +367,15 @@ impl ConnectionErrorHandler {
         match code {
             3000 => "No response body",
             3001 => "The server did not return 200",
+            3002 => "The response body is not valid JSON",
+            3003 => "The response is missing the $umid",
+            3004 => "A stream in the response is missing its id",
+            3005 => "The response did not include a new stream",
+            3006 => "The node redirected the request",
+            3007 => "The response is larger than the maximum response size",
+            3008 => "The contract rejected the transaction",
+            3009 => "The transaction was not committed",
+            3010 => "A transaction signature could not be verified",
             _ => "Unknown Error",
         }
     }
@@ -104,6 +390,8 @@ impl ConnectionErrorHandler {
             4005 => "Error creating public key for transaction encryption",
             4006 => "Error generating RSA key for encryption",
             4007 => "Error encrypting transaction",
+            4008 => "The transaction is empty",
+            4009 => "Timed out fetching the node key",
             _ => "Unknown Error",
         }
     }
@@ -111,7 +399,98 @@ impl ConnectionErrorHandler {
     fn get_encoding_error(code: &u16) -> &str {
         match code {
             5000 => "Error generating JSON",
+            5001 => "Error compressing the transaction",
+            5002 => "Error creating the transaction",
+            _ => "Unknown Error",
+        }
+    }
+}
+
+struct TransactionErrorHandler;
+
+impl TransactionErrorHandler {
+    fn get_data_error(code: &u16) -> &str {
+        match code {
+            1000 => "Transaction data is not valid JSON",
+            1001 => "Transaction data is missing the $tx object",
+            1002 => "Transaction $sigs is not an object",
+            1003 => "Transaction data is not a JSON object",
+            1004 => "Transaction namespace is empty",
+            1005 => "Transaction contract is empty",
+            1006 => "Transaction $tx is missing the $i object",
+            1007 => "Transaction inputs and signatures don't match",
+            1008 => "The identity has already signed the transaction",
             _ => "Unknown Error",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_source() {
+        let json_error = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let error = ConnectionError::EncodingError(5000, Some(Arc::new(json_error)));
+
+        assert_eq!(
+            error.to_string(),
+            "Encoding Error - 5000: Error generating JSON"
+        );
+        assert!(error.source().is_some());
+
+        let error = ConnectionError::HttpError(1000, None);
+        assert!(error.source().is_none());
+    }
+
+    #[test]
+    fn error_eq() {
+        let json_error = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let error = ConnectionError::EncodingError(5000, Some(Arc::new(json_error)));
+
+        assert_eq!(error, ConnectionError::EncodingError(5000, None));
+        assert_eq!(error.clone(), error);
+        assert_ne!(error, ConnectionError::EncodingError(5001, None));
+        assert_ne!(error, ConnectionError::HttpError(5000, None));
+
+        assert_eq!(
+            TransactionError::SigningError(KeyError::SigningError(2000)),
+            TransactionError::SigningError(KeyError::SigningError(2000))
+        );
+        assert_ne!(
+            TransactionError::DataError(1000, None),
+            TransactionError::DataError(1001, None)
+        );
+    }
+
+    #[test]
+    fn error_from() {
+        let json_error = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let error = ConnectionError::from(json_error);
+
+        assert_eq!(error, ConnectionError::ResponseError(3002, None));
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "network")]
+    fn error_from_reqwest() {
+        let http_error = reqwest::Client::new().get("not a url").send().unwrap_err();
+        let error: ConnectionError = http_error.into();
+
+        assert_eq!(error, ConnectionError::HttpError(1003, None));
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    fn unexpected_response_truncated() {
+        let response = UnexpectedResponse::new("{\"status\":\"ok\"}");
+        assert_eq!(response.body(), "{\"status\":\"ok\"}");
+
+        let body = "\u{e9}".repeat(200);
+        let response = UnexpectedResponse::new(&body);
+        assert_eq!(response.body().len(), 256 + 3);
+        assert!(response.body().ends_with("..."));
+    }
+}
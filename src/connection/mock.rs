@@ -0,0 +1,133 @@
+/*
+ * MIT License (MIT)
+ * Copyright (c) 2019 Activeledger
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! # Mock
+//!
+//! A `TransactionSender` that returns canned responses instead of making
+//! HTTP requests. Only available with the `test-utils` feature.
+//!
+//! ## Example
+//! ```
+//! # use activeledger::{MockConnection, Transaction, TransactionSender};
+//! let mock = MockConnection::new();
+//! mock.push_response(r#"{"$umid": "umid"}"#);
+//!
+//! let tx = Transaction::new(r#"{"$tx": {}, "$sigs": {}}"#);
+//! let body = mock.send_transaction(tx).unwrap();
+//!
+//! assert_eq!(body, r#"{"$umid": "umid"}"#);
+//! assert_eq!(mock.sent(), vec![r#"{"$tx": {}, "$sigs": {}}"#.to_string()]);
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use super::error::{ConnectionError, ConnectionResult};
+use super::sender::TransactionSender;
+use super::transaction::Transaction;
+
+/// Returns queued responses in order and records the transactions it is sent
+///
+/// Responses are queued with `push_response()` and `push_error()`. Sending a
+/// transaction when the queue is empty returns ResponseError 3000.
+#[derive(Debug, Default)]
+pub struct MockConnection {
+    responses: Mutex<VecDeque<ConnectionResult<String>>>,
+    sent: Mutex<Vec<String>>,
+}
+
+impl MockConnection {
+    /// Create a mock with no queued responses
+    pub fn new() -> MockConnection {
+        MockConnection::default()
+    }
+
+    /// Queue a response body to return for a transaction
+    pub fn push_response(&self, body: &str) {
+        self.queue(Ok(body.to_string()));
+    }
+
+    /// Queue an error to return for a transaction
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{MockConnection, Transaction, TransactionSender};
+    /// # use activeledger::error::ConnectionError;
+    /// let mock = MockConnection::new();
+    /// mock.push_error(ConnectionError::HttpError(1000, None));
+    ///
+    /// assert!(mock.send_transaction(Transaction::new("{}")).is_err());
+    /// ```
+    pub fn push_error(&self, error: ConnectionError) {
+        self.queue(Err(error));
+    }
+
+    /// Get the data of every transaction sent so far, oldest first
+    pub fn sent(&self) -> Vec<String> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    fn queue(&self, response: ConnectionResult<String>) {
+        self.responses.lock().unwrap().push_back(response);
+    }
+}
+
+impl TransactionSender for MockConnection {
+    fn send_transaction(&self, tx: Transaction) -> ConnectionResult<String> {
+        self.sent.lock().unwrap().push(tx.get_data().to_string());
+
+        match self.responses.lock().unwrap().pop_front() {
+            Some(response) => response,
+            None => Err(ConnectionError::ResponseError(3000, None)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_responses_in_order() {
+        let mock = MockConnection::new();
+        mock.push_response("first");
+        mock.push_error(ConnectionError::HttpError(1000, None));
+
+        let sender: &dyn TransactionSender = &mock;
+
+        assert_eq!(
+            sender.send_transaction(Transaction::new("one")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            sender.send_transaction(Transaction::new("two")),
+            Err(ConnectionError::HttpError(1000, None))
+        );
+        assert_eq!(
+            sender.send_transaction(Transaction::new("three")),
+            Err(ConnectionError::ResponseError(3000, None))
+        );
+
+        assert_eq!(mock.sent(), vec!["one", "two", "three"]);
+    }
+}
@@ -21,22 +21,732 @@
  * SOFTWARE.
  */
 
+#[cfg(feature = "network")]
+mod builder;
+#[allow(clippy::module_inception)]
+#[cfg(feature = "network")]
 mod connection;
 pub mod error;
+#[cfg(any(test, feature = "test-utils"))]
+mod mock;
+pub mod response;
+mod sender;
 pub mod transaction;
 
-pub use connection::Connection;
+#[cfg(feature = "network")]
+pub use builder::ConnectionBuilder;
+#[cfg(feature = "network")]
+pub use connection::{Connection, OaepHash};
+#[cfg(feature = "test-utils")]
+pub use mock::MockConnection;
+pub use sender::TransactionSender;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "network"))]
 mod tests {
     use crate::*;
-    use serde_json;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    /// Respond to a single request with the given body, returning the request line and body
+    fn serve_once(body: &'static str) -> (String, thread::JoinHandle<(String, String)>) {
+        serve_response(format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        ))
+    }
+
+    /// Respond to one request with the given raw HTTP response
+    ///
+    /// Returns the request line and the body.
+    fn serve_response(response: String) -> (String, thread::JoinHandle<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let request = read_request(&stream);
+
+            stream.write_all(response.as_bytes()).unwrap();
+
+            request
+        });
+
+        (url, handle)
+    }
+
+    /// Read a whole request so the connection closes cleanly
+    ///
+    /// Returns the request line and the body.
+    fn read_request(stream: &TcpStream) -> (String, String) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            let header = header.to_lowercase();
+            if let Some(value) = header.strip_prefix("content-length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut request_body = vec![0; length];
+        reader.read_exact(&mut request_body).unwrap();
+
+        (request_line, String::from_utf8(request_body).unwrap())
+    }
 
     #[test]
     fn connection_url() {
         Connection::new("http://localhost:5260", false).unwrap();
     }
 
+    #[test]
+    fn connection_pool_unreachable() {
+        let urls = vec!["http://localhost:1", "http://localhost:2"];
+
+        let result = Connection::new_pool(urls, false);
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::HttpError(1001, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn connection_with_client_unreachable() {
+        let client = reqwest::Client::builder().build().unwrap();
+
+        let result = Connection::with_client("http://localhost:1", false, client);
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::HttpError(1001, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn connection_unchecked() {
+        let connection = Connection::new_unchecked("http://localhost:1", true).unwrap();
+
+        let result = connection.send_transaction(Transaction::new("{}"));
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::EncryptionError(4001, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn connection_shared_threads() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        // Answer every request with its namespace as the umid
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let (_, body) = read_request(&stream);
+
+                let sent: serde_json::Value = serde_json::from_str(&body).unwrap();
+                let body = format!(r#"{{"$umid": {}}}"#, sent["$tx"]["$namespace"]);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        let connection = Arc::new(Connection::new_unchecked(&url, false).unwrap());
+
+        let send = |connection: &Connection, namespace: String| {
+            let tx = Transaction::builder()
+                .namespace(&namespace)
+                .contract("contract")
+                .build()
+                .unwrap();
+
+            connection.send_and_check(tx).unwrap().umid
+        };
+
+        let handles: Vec<_> = (0..8)
+            .map(|index| {
+                let connection = Arc::clone(&connection);
+                thread::spawn(move || {
+                    let namespace = format!("thread{}", index);
+                    (send(&connection, namespace.clone()), namespace)
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let (umid, namespace) = handle.join().unwrap();
+            assert_eq!(umid, namespace);
+        }
+
+        let clone = connection.as_ref().clone();
+        assert_eq!(send(&clone, "clone".to_string()), "clone");
+    }
+
+    #[test]
+    fn connection_key_timeout() {
+        // Accepts connections but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let result = Connection::builder()
+            .url(&url)
+            .encrypt(true)
+            .timeout(Duration::from_millis(200))
+            .build();
+
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::EncryptionError(4009, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn connection_encrypt_header() {
+        let result = Connection::builder()
+            .url("http://localhost:1")
+            .encrypt_header("Not a header", "1")
+            .build();
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::HttpError(1007, Some(_)))
+        ));
+
+        let (url, handle) = serve_once("{}");
+        let connection = Connection::builder()
+            .url(&url)
+            .encrypt_header("X-Gateway-Encrypt", "true")
+            .build()
+            .unwrap();
+        handle.join().unwrap();
+
+        let (name, value) = &connection.encrypt_header;
+        assert_eq!(name, "x-gateway-encrypt");
+        assert_eq!(value, "true");
+    }
+
+    #[test]
+    fn connection_discover_peers() {
+        let (url, handle) = serve_once(
+            r#"{
+                "status": "alive",
+                "neighbourhood": [
+                    { "host": "127.0.0.1", "port": "5260" },
+                    { "host": "node-2", "port": 5260 },
+                    { "host": "127.0.0.1", "port": "5260" },
+                    { "port": 5260 }
+                ]
+            }"#,
+        );
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+
+        let peers = connection.discover_peers().unwrap();
+        assert!(handle.join().unwrap().0.starts_with("GET /a/status"));
+        assert_eq!(peers, ["http://127.0.0.1:5260", "http://node-2:5260"]);
+
+        let (url, handle) = serve_once(r#"{"status": "alive"}"#);
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+
+        assert!(connection.discover_peers().unwrap().is_empty());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn connection_territoriality() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        // Echo the territoriality back like a node does, returning the request body
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let (_, body) = read_request(&stream);
+
+            let response = r#"{"$umid": "umid", "$territoriality": "node-id"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+
+            body
+        });
+
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+        let tx = Transaction::builder()
+            .namespace("default")
+            .contract("contract")
+            .territoriality("node-id")
+            .build()
+            .unwrap();
+
+        let response = connection.send_and_check(tx).unwrap();
+        assert_eq!(response.territoriality, Some("node-id".to_string()));
+
+        let sent: serde_json::Value = serde_json::from_str(&handle.join().unwrap()).unwrap();
+        assert_eq!(sent["$territoriality"], "node-id");
+    }
+
+    #[test]
+    fn connection_lazy_node_key() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let key = openssl::rsa::Rsa::generate(2048).unwrap();
+        let pem = base64::encode(&key.public_key_to_pem().unwrap());
+
+        // Drop the first key request, then answer the retry and the transaction
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            read_request(&stream);
+            drop(stream);
+
+            let mut request_lines = Vec::new();
+            for body in [
+                format!(r#"{{"pem": "{}"}}"#, pem),
+                r#"{"$umid": "umid"}"#.to_string(),
+            ] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let (request_line, _) = read_request(&stream);
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+                request_lines.push(request_line);
+            }
+
+            request_lines
+        });
+
+        let connection = Connection::builder()
+            .url(&url)
+            .encrypt(true)
+            .lazy(true)
+            .build()
+            .unwrap();
+        assert!(!connection.has_node_key());
+
+        let tx = Transaction::builder()
+            .namespace("default")
+            .contract("contract")
+            .build()
+            .unwrap();
+
+        let response = connection.send_and_check(tx).unwrap();
+        assert_eq!(response.umid, "umid");
+        assert!(connection.has_node_key());
+
+        let request_lines = handle.join().unwrap();
+        assert!(request_lines[0].starts_with("GET /a/status"));
+        assert!(request_lines[1].starts_with("POST"));
+    }
+
+    #[test]
+    fn connection_with_node_key() {
+        let key = openssl::rsa::Rsa::generate(2048).unwrap();
+        let pem = String::from_utf8(key.public_key_to_pem().unwrap()).unwrap();
+
+        // Only the transaction is sent, the status endpoint isn't used
+        let (url, handle) = serve_once(r#"{"$umid": "umid"}"#);
+
+        let connection = Connection::new_with_node_key(&url, &pem).unwrap();
+        assert!(connection.has_node_key());
+
+        let tx = Transaction::builder()
+            .namespace("default")
+            .contract("contract")
+            .build()
+            .unwrap();
+
+        let response = connection.send_and_check(tx).unwrap();
+        assert_eq!(response.umid, "umid");
+        assert!(handle.join().unwrap().0.starts_with("POST"));
+
+        assert_eq!(
+            Connection::new_with_node_key(&url, "not a pem").err(),
+            Some(error::ConnectionError::EncryptionError(4005, None))
+        );
+
+        let ec = openssl::ec::EcKey::generate(
+            &openssl::ec::EcGroup::from_curve_name(openssl::nid::Nid::SECP256K1).unwrap(),
+        )
+        .unwrap();
+        let ec_pem = String::from_utf8(ec.public_key_to_pem().unwrap()).unwrap();
+
+        assert_eq!(
+            Connection::new_with_node_key(&url, &ec_pem).err(),
+            Some(error::ConnectionError::EncryptionError(4006, None))
+        );
+    }
+
+    #[test]
+    fn connection_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+
+        // Return the first line of the request the proxy receives
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(stream).read_line(&mut line).unwrap();
+            line
+        });
+
+        let result = Connection::builder()
+            .url("http://activeledger.invalid:5260")
+            .proxy(&proxy)
+            .timeout(Duration::from_millis(500))
+            .build();
+
+        assert!(result.is_err());
+        assert!(handle
+            .join()
+            .unwrap()
+            .starts_with("GET http://activeledger.invalid:5260/a/status"));
+
+        let result = Connection::builder()
+            .url("http://localhost:5260")
+            .proxy("not a url")
+            .build();
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::UrlError(2002, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn connection_check() {
+        let (url, handle) = serve_once("{}");
+
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+        assert!(connection.is_healthy());
+        assert!(handle.join().unwrap().0.starts_with("GET /a/status"));
+
+        let connection = Connection::new_unchecked("http://localhost:1", false).unwrap();
+        assert!(!connection.is_healthy());
+        assert!(matches!(
+            connection.check(),
+            Err(error::ConnectionError::HttpError(1001, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn connection_supports_encryption() {
+        let (url, handle) = serve_once(r#"{"status": "alive", "pem": "key"}"#);
+        assert!(Connection::supports_encryption(&url).unwrap());
+        assert!(handle.join().unwrap().0.starts_with("GET /a/status"));
+
+        let (url, handle) = serve_once(r#"{"status": "alive"}"#);
+        assert!(!Connection::supports_encryption(&url).unwrap());
+        handle.join().unwrap();
+
+        assert!(matches!(
+            Connection::supports_encryption("http://localhost:1"),
+            Err(error::ConnectionError::EncryptionError(4001, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn connection_timed() {
+        let (url, handle) = serve_once(r#"{"$umid": "umid"}"#);
+
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+        let response = connection
+            .send_transaction_timed(Transaction::new("{}"))
+            .unwrap();
+
+        assert_eq!(response.body, r#"{"$umid": "umid"}"#);
+        assert!(response.elapsed > Duration::from_secs(0));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn connection_redirect() {
+        let (url, handle) = serve_response(String::from(
+            "HTTP/1.1 301 Moved Permanently\r\nLocation: https://node.example\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        ));
+
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+        let error = connection
+            .send_transaction(Transaction::new("{}"))
+            .unwrap_err();
+
+        assert_eq!(error, error::ConnectionError::ResponseError(3006, None));
+
+        let redirect = std::error::Error::source(&error)
+            .and_then(|source| source.downcast_ref::<error::Redirect>())
+            .unwrap();
+        assert_eq!(redirect.status(), 301);
+        assert_eq!(redirect.location(), Some("https://node.example"));
+
+        // The redirect was not followed
+        assert!(handle.join().unwrap().0.starts_with("POST /"));
+    }
+
+    #[test]
+    fn connection_max_response_size() {
+        let (url, handle) = serve_once(r#"{"$umid": "a long enough umid"}"#);
+
+        let mut connection = Connection::new_unchecked(&url, false).unwrap();
+        connection.max_response_size = 20;
+
+        let result = connection.send_transaction(Transaction::new("{}"));
+        assert_eq!(
+            result,
+            Err(error::ConnectionError::ResponseError(3007, None))
+        );
+        handle.join().unwrap();
+
+        // Without a Content-Length the limit is applied while reading
+        let (url, handle) = serve_response(String::from(
+            "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n{\"$umid\": \"a long enough umid\"}",
+        ));
+
+        let mut connection = Connection::new_unchecked(&url, false).unwrap();
+        connection.max_response_size = 20;
+
+        let result = connection.send_transaction(Transaction::new("{}"));
+        assert_eq!(
+            result,
+            Err(error::ConnectionError::ResponseError(3007, None))
+        );
+        handle.join().unwrap();
+
+        let (url, handle) = serve_once(r#"{"$umid": "umid"}"#);
+
+        let mut connection = Connection::new_unchecked(&url, false).unwrap();
+        connection.max_response_size = 20;
+
+        let body = connection.send_transaction(Transaction::new("{}")).unwrap();
+        assert_eq!(body, r#"{"$umid": "umid"}"#);
+        handle.join().unwrap();
+
+        // The largest limit reads the whole body
+        let (url, handle) = serve_response(String::from(
+            "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n{\"$umid\": \"umid\"}",
+        ));
+
+        let connection = Connection::builder()
+            .url(&url)
+            .max_response_size(u64::MAX)
+            .lazy(true)
+            .build()
+            .unwrap();
+
+        let body = connection.send_transaction(Transaction::new("{}")).unwrap();
+        assert_eq!(body, r#"{"$umid": "umid"}"#);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn connection_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        // Answer the status check made while building, then never answer the transaction
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            read_request(&stream);
+            let status = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+            stream.write_all(status.as_bytes()).unwrap();
+
+            let (stream, _) = listener.accept().unwrap();
+            read_request(&stream);
+            thread::sleep(Duration::from_secs(1));
+        });
+
+        let connection = Connection::builder()
+            .url(&url)
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let result = connection.send_transaction(Transaction::new("{}"));
+
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::HttpError(1006, Some(_)))
+        ));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn connection_timeout_no_failover() {
+        let stalled = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stalled_url = format!("http://{}", stalled.local_addr().unwrap());
+        let other = TcpListener::bind("127.0.0.1:0").unwrap();
+        let other_url = format!("http://{}", other.local_addr().unwrap());
+
+        // Receive the transaction without answering, returning if it was sent again
+        let handle = thread::spawn(move || {
+            let (stream, _) = stalled.accept().unwrap();
+            read_request(&stream);
+            thread::sleep(Duration::from_secs(1));
+
+            stalled.set_nonblocking(true).unwrap();
+            stalled.accept().is_ok()
+        });
+
+        let connection = Connection::builder()
+            .url(&stalled_url)
+            .url(&other_url)
+            .timeout(Duration::from_millis(200))
+            .lazy(true)
+            .build()
+            .unwrap();
+
+        let result = connection.send_transaction(Transaction::new("{}"));
+
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::HttpError(1006, Some(_)))
+        ));
+        assert!(!handle.join().unwrap());
+
+        other.set_nonblocking(true).unwrap();
+        assert!(other.accept().is_err());
+    }
+
+    #[test]
+    fn connection_body_timeout_no_failover() {
+        let stalled = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stalled_url = format!("http://{}", stalled.local_addr().unwrap());
+        let other = TcpListener::bind("127.0.0.1:0").unwrap();
+        let other_url = format!("http://{}", other.local_addr().unwrap());
+
+        // Accept the transaction and start the body, then stop sending
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = stalled.accept().unwrap();
+            read_request(&stream);
+            let response = "HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n{";
+            stream.write_all(response.as_bytes()).unwrap();
+            thread::sleep(Duration::from_secs(1));
+
+            stalled.set_nonblocking(true).unwrap();
+            stalled.accept().is_ok()
+        });
+
+        let connection = Connection::builder()
+            .url(&stalled_url)
+            .url(&other_url)
+            .timeout(Duration::from_millis(200))
+            .lazy(true)
+            .build()
+            .unwrap();
+
+        let result = connection.send_transaction(Transaction::new("{}"));
+
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::HttpError(1006, Some(_)))
+        ));
+        assert!(!handle.join().unwrap());
+
+        other.set_nonblocking(true).unwrap();
+        assert!(other.accept().is_err());
+    }
+
+    #[test]
+    fn connection_refused_failover() {
+        let (url, handle) = serve_once(r#"{"$umid": "umid"}"#);
+
+        let connection = Connection::builder()
+            .url("http://127.0.0.1:1")
+            .url(&url)
+            .lazy(true)
+            .build()
+            .unwrap();
+
+        let body = connection.send_transaction(Transaction::new("{}")).unwrap();
+
+        assert_eq!(body, r#"{"$umid": "umid"}"#);
+        assert!(handle.join().unwrap().0.starts_with("POST"));
+    }
+
+    #[test]
+    fn connection_sign_and_send() {
+        let (url, handle) = serve_once(r#"{"$umid": "umid"}"#);
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+        let key = key::EllipticCurve::new("identity").unwrap();
+
+        let tx_body = serde_json::json!({
+            "$namespace": "default",
+            "$contract": "contract",
+            "$i": { "identity": {} }
+        });
+
+        let body = connection
+            .sign_and_send_selfsigned(tx_body.clone(), "identity", &key)
+            .unwrap();
+        assert_eq!(body, r#"{"$umid": "umid"}"#);
+        handle.join().unwrap();
+
+        let result = connection.sign_and_send(serde_json::json!("not an object"), "identity", &key);
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::EncodingError(5002, Some(_)))
+        ));
+    }
+
+    #[test]
+    fn connection_streaming() {
+        let (url, handle) = serve_once(r#"{"$umid": "umid"}"#);
+
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+        let response = connection
+            .send_transaction_streaming(Transaction::new("{}"))
+            .unwrap();
+
+        let data: serde_json::Value = serde_json::from_reader(response).unwrap();
+        assert_eq!(data["$umid"], "umid");
+        assert!(handle.join().unwrap().0.starts_with("POST / "));
+    }
+
+    #[test]
+    fn connection_refresh_node_key() {
+        let (url, handle) = serve_once(r#"{"pem": "key"}"#);
+
+        let connection = Connection::new_unchecked(&url, true).unwrap();
+        connection.refresh_node_key().unwrap();
+        assert!(handle.join().unwrap().0.starts_with("GET /a/status"));
+
+        let connection = Connection::new_unchecked("http://localhost:1", true).unwrap();
+        assert!(matches!(
+            connection.refresh_node_key(),
+            Err(error::ConnectionError::EncryptionError(4001, Some(_)))
+        ));
+
+        // Nothing is fetched without encryption
+        let connection = Connection::new_unchecked("http://localhost:1", false).unwrap();
+        assert!(connection.refresh_node_key().is_ok());
+    }
+
+    #[test]
+    fn connection_pool_empty() {
+        let result = Connection::new_pool(Vec::new(), false);
+        assert!(matches!(
+            result,
+            Err(error::ConnectionError::UrlError(2001, None))
+        ));
+    }
+
     #[test]
     fn connection_onboard_rsa() {
         let connection = Connection::new("http://localhost:5260", false).unwrap();
@@ -74,7 +784,7 @@ mod tests {
 
         let signature = key.sign(&tx_body.to_string()).unwrap();
 
-        tx["$tx"] = tx_body.into();
+        tx["$tx"] = tx_body;
         tx["$sigs"]["rsa"] = signature.into();
 
         let tx = Transaction::new(&tx.to_string());
@@ -121,7 +831,7 @@ mod tests {
 
         let signature = key.sign(&tx_body.to_string()).unwrap();
 
-        tx["$tx"] = tx_body.into();
+        tx["$tx"] = tx_body;
         tx["$sigs"]["ec"] = signature.into();
 
         let tx = Transaction::new(&tx.to_string());
@@ -131,6 +841,90 @@ mod tests {
         assert!(data_obj["$streams"].to_string().chars().count() > 0);
     }
 
+    #[test]
+    fn connection_onboard() {
+        let (url, handle) = serve_once(
+            r#"{
+                "$umid": "umid",
+                "$summary": { "total": 1, "vote": 1, "commit": 1 },
+                "$streams": { "new": [{ "id": "stream-id", "name": "Test" }], "updated": [] }
+            }"#,
+        );
+
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+        let key = crate::key::EllipticCurve::new("Test").unwrap();
+
+        let stream_id = connection.onboard(&key).unwrap();
+        assert_eq!(stream_id, "stream-id");
+
+        let (request_line, body) = handle.join().unwrap();
+        assert!(request_line.starts_with("POST"));
+
+        let sent: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(sent["$selfsign"], true);
+        assert!(sent["$sigs"]["Test"].is_string());
+    }
+
+    #[test]
+    fn connection_batch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        // The second transaction fails, the others succeed
+        let handle = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for (status, body) in [
+                ("200 OK", r#"{"$umid": "one"}"#),
+                ("500 Internal Server Error", ""),
+                ("200 OK", r#"{"$umid": "three"}"#),
+            ] {
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+
+                let (mut stream, _) = listener.accept().unwrap();
+                let (_, body) = read_request(&stream);
+                stream.write_all(response.as_bytes()).unwrap();
+                bodies.push(body);
+            }
+
+            bodies
+        });
+
+        let connection = Connection::new_unchecked(&url, false).unwrap();
+
+        let transactions = ["one", "two", "three"]
+            .iter()
+            .map(|namespace| {
+                Transaction::builder()
+                    .namespace(namespace)
+                    .contract("contract")
+                    .build()
+                    .unwrap()
+            })
+            .collect();
+
+        let responses = connection.send_transactions(transactions);
+
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0].as_ref().unwrap(), r#"{"$umid": "one"}"#);
+        assert!(matches!(
+            responses[1],
+            Err(error::ConnectionError::ResponseError(3001, None))
+        ));
+        assert_eq!(responses[2].as_ref().unwrap(), r#"{"$umid": "three"}"#);
+
+        // Sent in order
+        let bodies = handle.join().unwrap();
+        for (body, namespace) in bodies.iter().zip(["one", "two", "three"]) {
+            let sent: serde_json::Value = serde_json::from_str(body).unwrap();
+            assert_eq!(sent["$tx"]["$namespace"], namespace);
+        }
+    }
+
     #[test]
     fn connection_enc_tx() {
         let connection = Connection::new("http://localhost:5270", true).unwrap();
@@ -152,7 +946,7 @@ mod tests {
                         }
                     }"#;
 
-        let tx = Transaction::new(&tx);
+        let tx = Transaction::new(tx);
         let res = connection.send_transaction(tx).unwrap();
         println!("Response {}", res);
 
@@ -0,0 +1,510 @@
+/*
+ * MIT License (MIT)
+ * Copyright (c) 2019 Activeledger
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! # Response
+//!
+//! Typed access to the response returned by an Activeledger node after
+//! processing a transaction.
+//!
+//! ## Example
+//! ```
+//! # use activeledger::response::TransactionResponse;
+//! let body = r#"{
+//!     "$umid": "umid",
+//!     "$summary": { "total": 1, "vote": 1, "commit": 1 },
+//!     "$streams": { "new": [{ "id": "stream-id", "name": "identity" }], "updated": [] }
+//! }"#;
+//!
+//! let response = TransactionResponse::parse(body).unwrap();
+//!
+//! assert_eq!(response.streams.new[0].id, "stream-id");
+//! ```
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use super::error::{ConnectionError::ResponseError, ConnectionResult, NodeErrors};
+
+/// A parsed transaction response
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionResponse {
+    /// The transactions unique id
+    pub umid: String,
+    pub summary: Summary,
+    pub streams: Streams,
+    /// Any data returned by the contract, empty if there was none
+    pub responses: Vec<Value>,
+    /// The node that processed the transaction, when the response includes
+    /// `$territoriality`
+    pub territoriality: Option<String>,
+}
+
+/// The `$summary` section of a response
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Summary {
+    /// Number of nodes in the network
+    pub total: u64,
+    /// Number of nodes that voted for the transaction
+    pub vote: u64,
+    /// Number of nodes that committed the transaction
+    pub commit: u64,
+    /// Errors returned by the network, empty if there were none
+    pub errors: Vec<String>,
+}
+
+/// The `$streams` section of a response
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Streams {
+    /// Streams created by the transaction
+    pub new: Vec<StreamInfo>,
+    /// Streams updated by the transaction
+    pub updated: Vec<StreamInfo>,
+}
+
+/// Whether a stream was created or updated by a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamRole {
+    New,
+    Updated,
+}
+
+/// A stream listed in the `$streams` section of a response
+///
+/// Neither value can be computed before the transaction is processed. The id
+/// is generated by the node and the name is whatever the contract passed when
+/// it created the stream, the input label is not used unless the contract chose
+/// to. Read them from the response rather than predicting them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamInfo {
+    /// The stream id, assigned by the node
+    pub id: String,
+    /// The name given by the contract, if it set one
+    pub name: Option<String>,
+}
+
+impl Streams {
+    /// Get the id of every new and updated stream with its role
+    ///
+    /// New streams are listed first, each list keeps the order used by the node.
+    pub fn all(&self) -> Vec<(StreamRole, String)> {
+        let new = self
+            .new
+            .iter()
+            .map(|stream| (StreamRole::New, stream.id.clone()));
+
+        let updated = self
+            .updated
+            .iter()
+            .map(|stream| (StreamRole::Updated, stream.id.clone()));
+
+        new.chain(updated).collect()
+    }
+}
+
+/// A response body with the time taken to get it
+///
+/// Returned by `Connection::send_transaction_timed()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedResponse {
+    /// The response body, as returned by `send_transaction()`
+    pub body: String,
+    /// Time from sending the transaction to reading the whole response
+    pub elapsed: Duration,
+}
+
+impl TransactionResponse {
+    /// Parse the body of a transaction response
+    ///
+    /// Missing `$summary`, `$streams` and `$responses` sections are treated as empty.
+    ///
+    /// ## Errors
+    /// Returns ResponseError 3002 if the body is not valid JSON, 3003 if `$umid`
+    /// is missing and 3004 if a stream entry has no id.
+    pub fn parse(body: &str) -> ConnectionResult<TransactionResponse> {
+        let data: Value = serde_json::from_str(body)?;
+
+        let umid = match data["$umid"].as_str() {
+            Some(umid) => umid.to_string(),
+            None => return Err(ResponseError(3003, None)),
+        };
+
+        let summary = &data["$summary"];
+
+        let errors = match summary["errors"].as_array() {
+            Some(errors) => errors
+                .iter()
+                .map(|error| match error.as_str() {
+                    Some(error) => error.to_string(),
+                    None => error.to_string(),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let summary = Summary {
+            total: summary["total"].as_u64().unwrap_or(0),
+            vote: summary["vote"].as_u64().unwrap_or(0),
+            commit: summary["commit"].as_u64().unwrap_or(0),
+            errors,
+        };
+
+        let streams = Streams {
+            new: TransactionResponse::parse_streams(&data["$streams"]["new"])?,
+            updated: TransactionResponse::parse_streams(&data["$streams"]["updated"])?,
+        };
+
+        let responses = match data["$responses"].as_array() {
+            Some(responses) => responses.clone(),
+            None => Vec::new(),
+        };
+
+        let territoriality = data["$territoriality"].as_str().map(String::from);
+
+        Ok(TransactionResponse {
+            umid,
+            summary,
+            streams,
+            responses,
+            territoriality,
+        })
+    }
+
+    /// Check whether the transaction was committed
+    ///
+    /// Separates transactions rejected by the contract from those that failed
+    /// later, which matters when deciding whether to retry. The contract runs
+    /// while the nodes vote, so a transaction that no node voted for was rejected
+    /// by the contract and sending it again gives the same result. A transaction
+    /// that was voted for but not committed by any node failed to reach
+    /// consensus or to commit, and can be retried.
+    /// Responses without a `$summary` are treated as committed.
+    ///
+    /// Transactions rejected because a signature could not be verified are
+    /// reported separately. This is usually a self signed transaction sent
+    /// without `$selfsign`, or signed with a key that doesn't match the
+    /// identity in `$sigs`. Nodes report this as a text message, so it is
+    /// detected by looking for "signature" in the node's errors.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::response::TransactionResponse;
+    /// let body = r#"{
+    ///     "$umid": "umid",
+    ///     "$summary": { "total": 3, "vote": 0, "commit": 0, "errors": ["Not allowed"] }
+    /// }"#;
+    ///
+    /// let response = TransactionResponse::parse(body).unwrap();
+    ///
+    /// assert!(response.check().is_err());
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns ResponseError 3010 if a signature could not be verified, 3008 if
+    /// the contract rejected the transaction and 3009 if it was not committed.
+    /// The error's source holds the node's errors.
+    pub fn check(&self) -> ConnectionResult<()> {
+        let summary = &self.summary;
+
+        if summary.total == 0 || summary.commit > 0 {
+            return Ok(());
+        }
+
+        let errors = Some(Arc::new(NodeErrors::new(&summary.errors)) as _);
+
+        let signature_error = summary
+            .errors
+            .iter()
+            .any(|error| error.to_lowercase().contains("signature"));
+
+        if summary.vote == 0 && signature_error {
+            Err(ResponseError(3010, errors))
+        } else if summary.vote == 0 {
+            Err(ResponseError(3008, errors))
+        } else {
+            Err(ResponseError(3009, errors))
+        }
+    }
+
+    /// Get the id of every stream affected by the transaction with its role
+    ///
+    /// See `Streams::all()`.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::response::{StreamRole, TransactionResponse};
+    /// let body = r#"{
+    ///     "$umid": "umid",
+    ///     "$streams": { "new": [{ "id": "created" }], "updated": [{ "id": "changed" }] }
+    /// }"#;
+    ///
+    /// let response = TransactionResponse::parse(body).unwrap();
+    ///
+    /// for (role, id) in response.all_streams() {
+    ///     if role == StreamRole::New {
+    ///         println!("Created {}", id);
+    ///     }
+    /// }
+    /// ```
+    pub fn all_streams(&self) -> Vec<(StreamRole, String)> {
+        self.streams.all()
+    }
+
+    /// Summarise the response on a single line, for example to show in a CLI
+    ///
+    /// Lists the umid, the new and updated streams and any errors. Stream
+    /// names are shown after their id and empty sections are shown as `none`.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::response::TransactionResponse;
+    /// let body = r#"{
+    ///     "$umid": "umid",
+    ///     "$streams": { "new": [{ "id": "stream-id", "name": "identity" }] }
+    /// }"#;
+    ///
+    /// let response = TransactionResponse::parse(body).unwrap();
+    ///
+    /// assert_eq!(
+    ///     response.summary_string(),
+    ///     "umid: umid | new: stream-id (identity) | updated: none | errors: none"
+    /// );
+    /// ```
+    pub fn summary_string(&self) -> String {
+        let errors = if self.summary.errors.is_empty() {
+            "none".to_string()
+        } else {
+            self.summary.errors.join("; ")
+        };
+
+        format!(
+            "umid: {} | new: {} | updated: {} | errors: {}",
+            self.umid,
+            TransactionResponse::list_streams(&self.streams.new),
+            TransactionResponse::list_streams(&self.streams.updated),
+            errors
+        )
+    }
+}
+
+// Private functions
+
+impl TransactionResponse {
+    /// List stream ids with their names, separated by commas
+    fn list_streams(streams: &[StreamInfo]) -> String {
+        if streams.is_empty() {
+            return "none".to_string();
+        }
+
+        let streams: Vec<String> = streams
+            .iter()
+            .map(|stream| match &stream.name {
+                Some(name) => format!("{} ({})", stream.id, name),
+                None => stream.id.clone(),
+            })
+            .collect();
+
+        streams.join(", ")
+    }
+
+    /// Convert a list of streams, a missing list is treated as empty
+    fn parse_streams(streams: &Value) -> ConnectionResult<Vec<StreamInfo>> {
+        let streams = match streams.as_array() {
+            Some(streams) => streams,
+            None => return Ok(Vec::new()),
+        };
+
+        let mut stream_info = Vec::with_capacity(streams.len());
+
+        for stream in streams {
+            let id = match stream["id"].as_str() {
+                Some(id) => id.to_string(),
+                None => return Err(ResponseError(3004, None)),
+            };
+
+            stream_info.push(StreamInfo {
+                id,
+                name: stream["name"].as_str().map(String::from),
+            });
+        }
+
+        Ok(stream_info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_onboard_response() {
+        let body = r#"{
+            "$umid": "umid",
+            "$summary": { "total": 3, "vote": 3, "commit": 3 },
+            "$streams": {
+                "new": [{ "id": "new-stream", "name": "identity" }],
+                "updated": [{ "id": "updated-stream" }]
+            }
+        }"#;
+
+        let response = TransactionResponse::parse(body).unwrap();
+
+        assert_eq!(response.umid, "umid");
+        assert_eq!(response.summary.commit, 3);
+        assert!(response.summary.errors.is_empty());
+
+        assert_eq!(
+            response.streams.new,
+            vec![StreamInfo {
+                id: "new-stream".to_string(),
+                name: Some("identity".to_string()),
+            }]
+        );
+        assert_eq!(response.streams.updated[0].name, None);
+        assert_eq!(response.territoriality, None);
+    }
+
+    #[test]
+    fn check_response() {
+        let response = TransactionResponse::parse(
+            r#"{ "$umid": "umid", "$summary": { "total": 3, "vote": 3, "commit": 3 } }"#,
+        )
+        .unwrap();
+        assert!(response.check().is_ok());
+
+        let response = TransactionResponse::parse(r#"{ "$umid": "umid" }"#).unwrap();
+        assert!(response.check().is_ok());
+
+        let response = TransactionResponse::parse(
+            r#"{
+                "$umid": "umid",
+                "$summary": { "total": 3, "vote": 0, "commit": 0, "errors": ["Rejected"] }
+            }"#,
+        )
+        .unwrap();
+
+        let error = response.check().unwrap_err();
+        assert_eq!(error, ResponseError(3008, None));
+
+        let source = std::error::Error::source(&error)
+            .and_then(|source| source.downcast_ref::<NodeErrors>())
+            .unwrap();
+        assert_eq!(source.errors(), ["Rejected".to_string()]);
+
+        let response = TransactionResponse::parse(
+            r#"{ "$umid": "umid", "$summary": { "total": 3, "vote": 2, "commit": 0 } }"#,
+        )
+        .unwrap();
+        assert_eq!(response.check(), Err(ResponseError(3009, None)));
+    }
+
+    #[test]
+    fn check_signature_failure() {
+        let response = TransactionResponse::parse(
+            r#"{
+                "$umid": "umid",
+                "$summary": {
+                    "total": 3, "vote": 0, "commit": 0,
+                    "errors": ["Self signed signature not matching"]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(response.check(), Err(ResponseError(3010, None)));
+    }
+
+    #[test]
+    fn summary_string() {
+        let body = r#"{
+            "$umid": "umid",
+            "$summary": { "total": 3, "vote": 0, "commit": 0, "errors": ["First", "Second"] },
+            "$streams": {
+                "new": [{ "id": "first", "name": "identity" }, { "id": "second" }],
+                "updated": [{ "id": "third" }]
+            }
+        }"#;
+
+        let response = TransactionResponse::parse(body).unwrap();
+
+        assert_eq!(
+            response.summary_string(),
+            "umid: umid | new: first (identity), second | updated: third | errors: First; Second"
+        );
+    }
+
+    #[test]
+    fn all_streams() {
+        let body = r#"{
+            "$umid": "umid",
+            "$streams": {
+                "new": [{ "id": "first" }, { "id": "second" }],
+                "updated": [{ "id": "third" }]
+            }
+        }"#;
+
+        let response = TransactionResponse::parse(body).unwrap();
+
+        assert_eq!(
+            response.all_streams(),
+            vec![
+                (StreamRole::New, "first".to_string()),
+                (StreamRole::New, "second".to_string()),
+                (StreamRole::Updated, "third".to_string()),
+            ]
+        );
+
+        assert!(Streams::default().all().is_empty());
+    }
+
+    #[test]
+    fn parse_empty_streams() {
+        let body = r#"{
+            "$umid": "umid",
+            "$summary": { "total": 1, "vote": 0, "commit": 0, "errors": ["Vote failed"] },
+            "$streams": { "new": [], "updated": [] }
+        }"#;
+
+        let response = TransactionResponse::parse(body).unwrap();
+
+        assert_eq!(response.streams, Streams::default());
+        assert_eq!(response.summary.errors, vec!["Vote failed".to_string()]);
+
+        let response = TransactionResponse::parse(r#"{ "$umid": "umid" }"#).unwrap();
+        assert_eq!(response.streams, Streams::default());
+    }
+
+    #[test]
+    fn parse_invalid_response() {
+        let result = TransactionResponse::parse("Not JSON");
+        assert!(matches!(result, Err(ResponseError(3002, Some(_)))));
+
+        let result = TransactionResponse::parse("{}");
+        assert!(matches!(result, Err(ResponseError(3003, None))));
+
+        let result =
+            TransactionResponse::parse(r#"{ "$umid": "umid", "$streams": { "new": [{}] } }"#);
+        assert!(matches!(result, Err(ResponseError(3004, None))));
+    }
+}
@@ -0,0 +1,63 @@
+/*
+ * MIT License (MIT)
+ * Copyright (c) 2019 Activeledger
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! # Sender
+//!
+//! Code that sends transactions can be written against `&dyn TransactionSender`
+//! instead of `Connection`, so it can be tested without an Activeledger node.
+//!
+//! ## Example
+//! ```
+//! # use activeledger::{Transaction, TransactionSender};
+//! # use activeledger::error::ConnectionResult;
+//! fn submit(sender: &dyn TransactionSender) -> ConnectionResult<String> {
+//!     let tx = Transaction::builder()
+//!         .namespace("default")
+//!         .contract("contract")
+//!         .build()
+//!         .unwrap();
+//!
+//!     sender.send_transaction(tx)
+//! }
+//! ```
+
+use super::error::ConnectionResult;
+use super::transaction::Transaction;
+#[cfg(feature = "network")]
+use super::Connection;
+
+/// Anything that can send a transaction and return the node's response body
+///
+/// Implemented by `Connection`. With the `test-utils` feature `MockConnection`
+/// implements it with canned responses.
+pub trait TransactionSender {
+    /// Send the transaction and return the response body
+    fn send_transaction(&self, tx: Transaction) -> ConnectionResult<String>;
+}
+
+#[cfg(feature = "network")]
+impl TransactionSender for Connection {
+    fn send_transaction(&self, tx: Transaction) -> ConnectionResult<String> {
+        Connection::send_transaction(self, tx)
+    }
+}
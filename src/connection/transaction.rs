@@ -21,12 +21,42 @@
  * SOFTWARE.
  */
 
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use serde_json::{Map, Value};
+
+use crate::canonical;
+use crate::key::{self, Key};
+
+use super::error::{
+    SignatureMismatch,
+    TransactionError::{DataError, SigningError},
+    TransactionResult,
+};
+
 /// Transaction struct
 pub struct Transaction {
     data: String,
 }
 
 impl Transaction {
+    /// Create a TransactionBuilder to build the transaction data
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Transaction;
+    /// let tx = Transaction::builder()
+    ///     .namespace("default")
+    ///     .contract("contract")
+    ///     .input("identity", serde_json::json!({ "value": 1 }))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> TransactionBuilder {
+        TransactionBuilder::new()
+    }
+
     /// Create a new transaction object
     ///
     /// Takes a JSON string
@@ -65,10 +95,971 @@ impl Transaction {
         }
     }
 
+    /// Create a new transaction from JSON data that is already parsed
+    ///
+    /// The value is serialized once, in the canonical form described in the
+    /// `canonical` module, avoiding a round trip through a string.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Transaction;
+    /// let data = serde_json::json!({
+    ///     "$tx": { "$namespace": "default", "$contract": "contract", "$i": {} },
+    ///     "$sigs": {}
+    /// });
+    ///
+    /// let tx = Transaction::from_value(data);
+    /// ```
+    pub fn from_value(tx_data: Value) -> Transaction {
+        Transaction {
+            data: canonical::to_string(&tx_data),
+        }
+    }
+
+    /// Create a signed transaction that onboards the given key
+    ///
+    /// The transaction uses the `onboard` contract in the `default` namespace,
+    /// the key's name is used as the identity name and the transaction is self signed.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{key::RSA, Transaction};
+    /// let key = RSA::new("keyname").unwrap();
+    ///
+    /// let tx = Transaction::onboard(&key).unwrap();
+    /// ```
+    pub fn onboard(key: &dyn Key) -> TransactionResult<Transaction> {
+        Transaction::onboard_as(key.name(), key)
+    }
+
+    /// Create a signed transaction that onboards the given key under an identity label
+    ///
+    /// Works like `onboard()`, using the given label instead of the key's name.
+    /// The label is used for both the `$i` input and the `$sigs` entry, the node
+    /// rejects a self signed onboard transaction where they differ.
+    /// The label only identifies the input within the transaction, the new
+    /// stream's id and name are returned in the response, see `response::StreamInfo`.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{key::RSA, Transaction};
+    /// let key = RSA::new("keyname").unwrap();
+    ///
+    /// let tx = Transaction::onboard_as("identity", &key).unwrap();
+    /// ```
+    pub fn onboard_as(identity: &str, key: &dyn Key) -> TransactionResult<Transaction> {
+        let public_pem = match key.get_pem() {
+            Ok(pem) => pem.public,
+            Err(error) => return Err(SigningError(error)),
+        };
+
+        let input = serde_json::json!({
+            "type": key.key_type(),
+            "publicKey": public_pem,
+        });
+
+        let mut tx = Transaction::builder()
+            .namespace("default")
+            .contract("onboard")
+            .input(identity, input)
+            .selfsign(true)
+            .build()?;
+
+        tx.sign(identity, key)?;
+
+        Ok(tx)
+    }
+
     /// Get the transaction data
     ///
     /// Returns a string reference
     pub fn get_data(&self) -> &str {
         &self.data
     }
+
+    /// Get the transaction data as a JSON value
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Transaction;
+    /// let tx = Transaction::new(r#"{"$tx": {"$namespace": "default"}, "$sigs": {}}"#);
+    ///
+    /// let data = tx.as_value().unwrap();
+    /// assert_eq!(data["$tx"]["$namespace"], "default");
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns DataError 1000 if the transaction data is not valid JSON.
+    pub fn as_value(&self) -> TransactionResult<Value> {
+        self.parse_data()
+    }
+
+    /// Sign the transaction with the given key
+    ///
+    /// The `$tx` object is signed and the signature is added to `$sigs`
+    /// under the given identity, any other signatures are left in place.
+    ///
+    /// The whole `$tx` object is signed, including the `$i`, `$o` and `$r` sections.
+    /// The signed data is the `$tx` object in the canonical form described in the
+    /// `canonical` module. The transaction data is re-serialized the same way so
+    /// the node verifies exactly the bytes that were signed.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{key::RSA, Transaction};
+    /// let key = RSA::new("keyname").unwrap();
+    ///
+    /// let mut tx = Transaction::new(r#"{"$tx": {"$namespace": "default"}, "$sigs": {}}"#);
+    ///
+    /// tx.sign("identity", &key).unwrap();
+    /// ```
+    pub fn sign(&mut self, identity: &str, key: &dyn Key) -> TransactionResult<()> {
+        self.add_signatures(&[(identity, key)])
+    }
+
+    /// Add a signature to a transaction that another party has already signed
+    ///
+    /// Works like `sign()`, but fails instead of replacing a signature the
+    /// identity already has. Every party signs the same canonical `$tx`, so
+    /// existing signatures stay valid as long as `$tx` is not changed.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{key::RSA, Transaction};
+    /// let first = RSA::new("first").unwrap();
+    /// let second = RSA::new("second").unwrap();
+    ///
+    /// let mut tx = Transaction::new(r#"{"$tx": {"$namespace": "default"}, "$sigs": {}}"#);
+    /// tx.sign("first", &first).unwrap();
+    ///
+    /// // Later, by the second party
+    /// tx.add_signature("second", &second).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns DataError 1008 if the identity has already signed, otherwise the
+    /// same errors as `sign()`.
+    pub fn add_signature(&mut self, identity: &str, key: &dyn Key) -> TransactionResult<()> {
+        let data = self.parse_data()?;
+
+        if data["$sigs"].get(identity).is_some() {
+            return Err(DataError(1008, None));
+        }
+
+        self.sign(identity, key)
+    }
+
+    /// Verify the signatures in `$sigs` without contacting a node
+    ///
+    /// Takes the public key PEM of each identity and checks its signature against
+    /// the canonical `$tx` object, the same data that is signed by `sign()`.
+    /// Returns whether each identity in `$sigs` verified. Identities without a public
+    /// key, with an invalid public key or with a malformed signature did not verify.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::collections::HashMap;
+    /// # use activeledger::{key::RSA, Transaction};
+    /// let key = RSA::new("keyname").unwrap();
+    ///
+    /// let mut tx = Transaction::new(r#"{"$tx": {"$namespace": "default"}, "$sigs": {}}"#);
+    /// tx.sign("identity", &key).unwrap();
+    ///
+    /// let mut public_keys = HashMap::new();
+    /// public_keys.insert("identity".to_string(), key.get_pem().unwrap().public);
+    ///
+    /// let verified = tx.verify_signatures(&public_keys).unwrap();
+    /// assert!(verified["identity"]);
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns DataError 1000 if the transaction data is not valid JSON, 1001 if
+    /// it doesn't have a `$tx` object and 1002 if `$sigs` is not an object.
+    pub fn verify_signatures(
+        &self,
+        public_keys: &HashMap<String, String>,
+    ) -> TransactionResult<BTreeMap<String, bool>> {
+        let data = self.parse_data()?;
+
+        if !data["$tx"].is_object() {
+            return Err(DataError(1001, None));
+        }
+
+        let sigs = match data["$sigs"].as_object() {
+            Some(sigs) => sigs,
+            None => return Err(DataError(1002, None)),
+        };
+
+        let signed_data = canonical::to_string(&data["$tx"]);
+
+        let verified = sigs
+            .iter()
+            .map(|(identity, signature)| {
+                let verified = match (public_keys.get(identity), signature.as_str()) {
+                    (Some(public_pem), Some(signature)) => {
+                        key::verify_with_public(public_pem, &signed_data, signature)
+                            .unwrap_or(false)
+                    }
+                    _ => false,
+                };
+
+                (identity.clone(), verified)
+            })
+            .collect();
+
+        Ok(verified)
+    }
+
+    /// Get the labels of the inputs in `$i`
+    ///
+    /// These are the identities that must sign the transaction, see
+    /// `TransactionBuilder::input`. The labels are returned in sorted order.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Transaction;
+    /// let tx = Transaction::new(r#"{"$tx": {"$i": {"second": {}, "first": {}}}, "$sigs": {}}"#);
+    ///
+    /// assert_eq!(tx.identities().unwrap(), vec!["first", "second"]);
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns DataError 1000 if the transaction data is not valid JSON, 1001 if
+    /// it doesn't have a `$tx` object and 1006 if `$tx` doesn't have an `$i` object.
+    pub fn identities(&self) -> TransactionResult<Vec<String>> {
+        let data = self.parse_data()?;
+
+        if !data["$tx"].is_object() {
+            return Err(DataError(1001, None));
+        }
+
+        match data["$tx"]["$i"].as_object() {
+            Some(inputs) => Ok(inputs.keys().cloned().collect()),
+            None => Err(DataError(1006, None)),
+        }
+    }
+
+    /// Check that every input in `$i` has a signature in `$sigs` and the reverse
+    ///
+    /// Nodes reject transactions where these don't match, this finds the mistake
+    /// before the transaction is sent.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::{key::RSA, Transaction};
+    /// let key = RSA::new("keyname").unwrap();
+    ///
+    /// let mut tx = Transaction::new(r#"{"$tx": {"$i": {"identity": {}}}, "$sigs": {}}"#);
+    /// assert!(tx.validate_signatures_present().is_err());
+    ///
+    /// tx.sign("identity", &key).unwrap();
+    /// assert!(tx.validate_signatures_present().is_ok());
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns the errors of `identities()`, DataError 1002 if `$sigs` is not an
+    /// object and 1007 if the identities don't match. The source of a 1007 error
+    /// is a `SignatureMismatch` naming the identities.
+    pub fn validate_signatures_present(&self) -> TransactionResult<()> {
+        let identities = self.identities()?;
+
+        let data = self.parse_data()?;
+        let sigs = match data["$sigs"].as_object() {
+            Some(sigs) => sigs,
+            None => return Err(DataError(1002, None)),
+        };
+
+        let unsigned: Vec<String> = identities
+            .iter()
+            .filter(|identity| !sigs.contains_key(identity.as_str()))
+            .cloned()
+            .collect();
+
+        let unexpected: Vec<String> = sigs
+            .keys()
+            .filter(|identity| !identities.contains(identity))
+            .cloned()
+            .collect();
+
+        if unsigned.is_empty() && unexpected.is_empty() {
+            return Ok(());
+        }
+
+        let mismatch = SignatureMismatch::new(unsigned, unexpected);
+        Err(DataError(1007, Some(Arc::new(mismatch))))
+    }
+
+    /// Set the node that should process the transaction
+    ///
+    /// Sets the `$territoriality` field, replacing any existing value.
+    /// The field is outside of `$tx` so it can be set before or after signing.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::Transaction;
+    /// let tx = Transaction::new(r#"{"$tx": {"$namespace": "default"}, "$sigs": {}}"#)
+    ///     .with_territoriality("node-id")
+    ///     .unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns DataError 1000 if the transaction data is not valid JSON and
+    /// 1003 if it is not a JSON object.
+    pub fn with_territoriality(mut self, node_id: &str) -> TransactionResult<Transaction> {
+        let mut data = self.parse_data()?;
+
+        match data.as_object_mut() {
+            Some(tx) => tx.insert("$territoriality".to_string(), node_id.into()),
+            None => return Err(DataError(1003, None)),
+        };
+
+        self.data = canonical::to_string(&data);
+
+        Ok(self)
+    }
+
+    /// Get the node set in `$territoriality`
+    ///
+    /// Returns None if the field is not set or the data is not valid JSON.
+    pub fn get_territoriality(&self) -> Option<String> {
+        let data = self.parse_data().ok()?;
+
+        data["$territoriality"].as_str().map(String::from)
+    }
+}
+
+/// Builder for transaction data
+///
+/// Builds the `$tx` object and an empty `$sigs` object, the transaction
+/// can then be signed using `Transaction::sign()`.
+/// The `$o` and `$r` sections are only included if they are used.
+#[derive(Debug, Clone, Default)]
+pub struct TransactionBuilder {
+    namespace: String,
+    contract: String,
+    entry: Option<String>,
+    inputs: Map<String, Value>,
+    outputs: Option<Map<String, Value>>,
+    reads: Option<Map<String, Value>>,
+    selfsign: bool,
+    territoriality: Option<String>,
+}
+
+impl TransactionBuilder {
+    /// Create a new, empty, builder
+    pub fn new() -> TransactionBuilder {
+        TransactionBuilder::default()
+    }
+
+    /// Set the contract namespace (`$namespace`)
+    pub fn namespace(mut self, namespace: &str) -> TransactionBuilder {
+        self.namespace = namespace.to_string();
+        self
+    }
+
+    /// Set the contract to run (`$contract`)
+    pub fn contract(mut self, contract: &str) -> TransactionBuilder {
+        self.contract = contract.to_string();
+        self
+    }
+
+    /// Set the contract entry point (`$entry`)
+    ///
+    /// Contracts with more than one entry point use this to choose which one runs,
+    /// when it is not set the contract's default entry point is used. The entry is
+    /// part of `$tx` so it is covered by the signatures.
+    pub fn entry(mut self, entry: &str) -> TransactionBuilder {
+        self.entry = Some(entry.to_string());
+        self
+    }
+
+    /// Add an input stream (`$i`)
+    pub fn input(mut self, stream: &str, value: Value) -> TransactionBuilder {
+        self.inputs.insert(stream.to_string(), value);
+        self
+    }
+
+    /// Add an output stream (`$o`)
+    pub fn output(mut self, stream: &str, value: Value) -> TransactionBuilder {
+        self.outputs
+            .get_or_insert_with(Map::new)
+            .insert(stream.to_string(), value);
+        self
+    }
+
+    /// Add a read only stream (`$r`)
+    pub fn read(mut self, stream: &str, value: Value) -> TransactionBuilder {
+        self.reads
+            .get_or_insert_with(Map::new)
+            .insert(stream.to_string(), value);
+        self
+    }
+
+    /// Mark the transaction as self signed (`$selfsign`)
+    pub fn selfsign(mut self, selfsign: bool) -> TransactionBuilder {
+        self.selfsign = selfsign;
+        self
+    }
+
+    /// Set the node that should process the transaction (`$territoriality`)
+    pub fn territoriality(mut self, node_id: &str) -> TransactionBuilder {
+        self.territoriality = Some(node_id.to_string());
+        self
+    }
+
+    /// Create the transaction
+    ///
+    /// ## Errors
+    /// Returns DataError 1004 if the namespace is empty and 1005 if the contract is empty.
+    pub fn build(self) -> TransactionResult<Transaction> {
+        if self.namespace.is_empty() {
+            return Err(DataError(1004, None));
+        }
+
+        if self.contract.is_empty() {
+            return Err(DataError(1005, None));
+        }
+
+        let mut tx = Map::new();
+        tx.insert("$namespace".to_string(), self.namespace.into());
+        tx.insert("$contract".to_string(), self.contract.into());
+
+        if let Some(entry) = self.entry {
+            tx.insert("$entry".to_string(), entry.into());
+        }
+
+        tx.insert("$i".to_string(), self.inputs.into());
+
+        if let Some(outputs) = self.outputs {
+            tx.insert("$o".to_string(), outputs.into());
+        }
+
+        if let Some(reads) = self.reads {
+            tx.insert("$r".to_string(), reads.into());
+        }
+
+        let mut data = Map::new();
+        data.insert("$tx".to_string(), tx.into());
+        data.insert("$sigs".to_string(), Map::new().into());
+
+        if self.selfsign {
+            data.insert("$selfsign".to_string(), true.into());
+        }
+
+        if let Some(territoriality) = self.territoriality {
+            data.insert("$territoriality".to_string(), territoriality.into());
+        }
+
+        Ok(Transaction::new(&canonical::to_string(&Value::Object(
+            data,
+        ))))
+    }
+}
+
+/// Builder for transactions signed by several identities
+///
+/// Collects the identities and their keys, then signs the transaction with each
+/// key and adds all of the signatures to `$sigs`. Every key signs the same
+/// canonical `$tx` data.
+///
+/// # Example
+/// ```
+/// # use activeledger::{key::{EllipticCurve, RSA}, SignatureBuilder, Transaction};
+/// let first = RSA::new("first").unwrap();
+/// let second = EllipticCurve::new("second").unwrap();
+///
+/// let mut tx = Transaction::new(r#"{"$tx": {"$namespace": "default"}, "$sigs": {}}"#);
+///
+/// SignatureBuilder::new()
+///     .signer("first-identity", &first)
+///     .signer("second-identity", &second)
+///     .sign(&mut tx)
+///     .unwrap();
+/// ```
+#[derive(Default)]
+pub struct SignatureBuilder<'a> {
+    signers: Vec<(String, &'a dyn Key)>,
+}
+
+impl<'a> SignatureBuilder<'a> {
+    /// Create a new builder with no signers
+    pub fn new() -> SignatureBuilder<'a> {
+        SignatureBuilder::default()
+    }
+
+    /// Add an identity and the key it signs with
+    ///
+    /// Adding the same identity twice replaces its signature.
+    pub fn signer(mut self, identity: &str, key: &'a dyn Key) -> SignatureBuilder<'a> {
+        self.signers.push((identity.to_string(), key));
+        self
+    }
+
+    /// Sign the transaction with every key
+    ///
+    /// Existing signatures for other identities are left in place. If any key
+    /// fails to sign, the transaction is not changed.
+    ///
+    /// ## Errors
+    /// The same errors as `Transaction::sign()` are returned.
+    pub fn sign(&self, tx: &mut Transaction) -> TransactionResult<()> {
+        let signers: Vec<(&str, &dyn Key)> = self
+            .signers
+            .iter()
+            .map(|(identity, key)| (identity.as_str(), *key))
+            .collect();
+
+        tx.add_signatures(&signers)
+    }
+}
+
+// Private functions
+
+impl Transaction {
+    /// Sign the $tx object using the given keys and store the signatures
+    fn add_signatures(&mut self, signers: &[(&str, &dyn Key)]) -> TransactionResult<()> {
+        let mut data = self.parse_data()?;
+
+        if !data["$tx"].is_object() {
+            return Err(DataError(1001, None));
+        }
+
+        // Every key signs the same data
+        let signed_data = canonical::to_string(&data["$tx"]);
+
+        let mut signatures = Vec::with_capacity(signers.len());
+
+        for (identity, key) in signers {
+            match key.sign(&signed_data) {
+                Ok(signature) => signatures.push((identity.to_string(), signature)),
+                Err(error) => return Err(SigningError(error)),
+            }
+        }
+
+        // Create the signature object if the transaction doesn't have one yet
+        if data["$sigs"].is_null() {
+            data["$sigs"] = serde_json::json!({});
+        }
+
+        let sigs = match data["$sigs"].as_object_mut() {
+            Some(sigs) => sigs,
+            None => return Err(DataError(1002, None)),
+        };
+
+        for (identity, signature) in signatures {
+            sigs.insert(identity, signature.into());
+        }
+
+        self.data = canonical::to_string(&data);
+
+        Ok(())
+    }
+
+    /// Parse the stored transaction data
+    fn parse_data(&self) -> TransactionResult<serde_json::Value> {
+        match serde_json::from_str(&self.data) {
+            Ok(data) => Ok(data),
+            Err(error) => Err(DataError(1000, Some(Arc::new(error)))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key::{EllipticCurve, RSA};
+
+    const TX: &str = r#"{
+        "$tx": {
+            "$namespace": "default",
+            "$contract": "onboard",
+            "$i": {
+                "identity": {
+                    "type": "rsa",
+                    "publicKey": ""
+                }
+            }
+        },
+        "$selfsign": true,
+        "$sigs": {}
+    }"#;
+
+    #[test]
+    fn tx_sign() {
+        let key = RSA::new("Test").unwrap();
+
+        let mut tx = Transaction::new(TX);
+        tx.sign("identity", &key).unwrap();
+
+        let data: serde_json::Value = serde_json::from_str(tx.get_data()).unwrap();
+        let signature = data["$sigs"]["identity"].as_str().unwrap();
+
+        assert!(key
+            .verify(&canonical::to_string(&data["$tx"]), signature)
+            .unwrap());
+    }
+
+    #[test]
+    fn tx_sign_ec() {
+        let key = EllipticCurve::new("Test").unwrap();
+
+        let mut tx = Transaction::new(TX);
+        tx.sign("identity", &key).unwrap();
+
+        let data: serde_json::Value = serde_json::from_str(tx.get_data()).unwrap();
+        let signature = data["$sigs"]["identity"].as_str().unwrap();
+
+        assert!(key
+            .verify(&canonical::to_string(&data["$tx"]), signature)
+            .unwrap());
+    }
+
+    #[test]
+    fn tx_sign_numbers() {
+        let key = EllipticCurve::new("Test").unwrap();
+
+        // Both the number format and the key order differ from serde's output
+        let mut tx = Transaction::new(
+            r#"{"$tx": {"$namespace": "default", "$i": {"identity": {"amount": 1.5e21, "10": 1, "2": 2}}}}"#,
+        );
+        tx.sign("identity", &key).unwrap();
+
+        let data: serde_json::Value = serde_json::from_str(tx.get_data()).unwrap();
+        let signature = data["$sigs"]["identity"].as_str().unwrap();
+        let signed = canonical::to_string(&data["$tx"]);
+
+        assert_ne!(signed, data["$tx"].to_string());
+        assert!(key.verify(&signed, signature).unwrap());
+        assert!(!key.verify(&data["$tx"].to_string(), signature).unwrap());
+    }
+
+    #[test]
+    fn tx_sign_invalid() {
+        let key = EllipticCurve::new("Test").unwrap();
+
+        let mut tx = Transaction::new("{TX DATA HERE}");
+        assert!(tx.sign("identity", &key).is_err());
+
+        let mut tx = Transaction::new(r#"{"$sigs": {}}"#);
+        assert!(tx.sign("identity", &key).is_err());
+    }
+
+    #[test]
+    fn tx_territoriality() {
+        let tx = Transaction::new(TX).with_territoriality("node").unwrap();
+        assert_eq!(tx.get_territoriality(), Some("node".to_string()));
+
+        let tx = tx.with_territoriality("other").unwrap();
+        assert_eq!(tx.get_territoriality(), Some("other".to_string()));
+
+        assert_eq!(Transaction::new(TX).get_territoriality(), None);
+    }
+
+    #[test]
+    fn tx_territoriality_invalid() {
+        let result = Transaction::new("{TX DATA HERE}").with_territoriality("node");
+        assert!(matches!(result, Err(DataError(1000, _))));
+
+        let result = Transaction::new("[]").with_territoriality("node");
+        assert!(matches!(result, Err(DataError(1003, None))));
+    }
+
+    #[test]
+    fn tx_onboard() {
+        let key = EllipticCurve::new("identity").unwrap();
+
+        let tx = Transaction::onboard(&key).unwrap();
+
+        let data: serde_json::Value = serde_json::from_str(tx.get_data()).unwrap();
+        let input = &data["$tx"]["$i"]["identity"];
+
+        assert_eq!(data["$tx"]["$contract"], "onboard");
+        assert_eq!(data["$selfsign"], true);
+        assert_eq!(input["type"], "secp256k1");
+        assert_eq!(input["publicKey"], key.get_pem().unwrap().public);
+
+        let signature = data["$sigs"]["identity"].as_str().unwrap();
+        assert!(key
+            .verify(&canonical::to_string(&data["$tx"]), signature)
+            .unwrap());
+
+        let key = RSA::new("identity").unwrap();
+        let data: serde_json::Value =
+            serde_json::from_str(Transaction::onboard(&key).unwrap().get_data()).unwrap();
+        assert_eq!(data["$tx"]["$i"]["identity"]["type"], "rsa");
+    }
+
+    #[test]
+    fn tx_onboard_as() {
+        let key = RSA::new("keyname").unwrap();
+
+        let tx = Transaction::onboard_as("label", &key).unwrap();
+        let data = tx.as_value().unwrap();
+
+        assert!(data["$tx"]["$i"]["label"].is_object());
+        assert!(data["$tx"]["$i"]["keyname"].is_null());
+
+        let mut public_keys = HashMap::new();
+        public_keys.insert("label".to_string(), key.get_pem().unwrap().public);
+
+        let verified = tx.verify_signatures(&public_keys).unwrap();
+        assert_eq!(verified.len(), 1);
+        assert!(verified["label"]);
+    }
+
+    #[test]
+    fn tx_builder() {
+        let tx = Transaction::builder()
+            .namespace("default")
+            .contract("contract")
+            .entry("entry")
+            .input("input", serde_json::json!({ "value": 1 }))
+            .output("output", serde_json::json!({}))
+            .selfsign(true)
+            .build()
+            .unwrap();
+
+        let data: Value = serde_json::from_str(tx.get_data()).unwrap();
+
+        assert_eq!(
+            data,
+            serde_json::json!({
+                "$tx": {
+                    "$namespace": "default",
+                    "$contract": "contract",
+                    "$entry": "entry",
+                    "$i": { "input": { "value": 1 } },
+                    "$o": { "output": {} }
+                },
+                "$selfsign": true,
+                "$sigs": {}
+            })
+        );
+    }
+
+    #[test]
+    fn tx_builder_invalid() {
+        let result = Transaction::builder().contract("contract").build();
+        assert!(matches!(result, Err(DataError(1004, None))));
+
+        let result = Transaction::builder().namespace("default").build();
+        assert!(matches!(result, Err(DataError(1005, None))));
+    }
+
+    #[test]
+    fn tx_sign_entry() {
+        let key = RSA::new("Test").unwrap();
+
+        let mut tx = Transaction::builder()
+            .namespace("default")
+            .contract("contract")
+            .entry("transfer")
+            .input("identity", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        tx.sign("identity", &key).unwrap();
+
+        let mut public_keys = HashMap::new();
+        public_keys.insert("identity".to_string(), key.get_pem().unwrap().public);
+        assert!(tx.verify_signatures(&public_keys).unwrap()["identity"]);
+
+        // Changing the entry invalidates the signature
+        let mut data = tx.as_value().unwrap();
+        data["$tx"]["$entry"] = "other".into();
+
+        let tx = Transaction::from_value(data);
+        assert!(!tx.verify_signatures(&public_keys).unwrap()["identity"]);
+    }
+
+    #[test]
+    fn tx_sign_all_sections() {
+        let key = RSA::new("Test").unwrap();
+
+        let mut tx = Transaction::builder()
+            .namespace("default")
+            .contract("contract")
+            .input("identity", serde_json::json!({ "value": 1 }))
+            .output("output", serde_json::json!({ "value": 2 }))
+            .read("read", serde_json::json!("stream"))
+            .build()
+            .unwrap();
+
+        tx.sign("identity", &key).unwrap();
+
+        let data: Value = serde_json::from_str(tx.get_data()).unwrap();
+        let signed = canonical::to_string(&data["$tx"]);
+
+        assert!(signed.contains(r#""$i":{"identity":{"value":1}}"#));
+        assert!(signed.contains(r#""$o":{"output":{"value":2}}"#));
+        assert!(signed.contains(r#""$r":{"read":"stream"}"#));
+
+        // The node verifies the $tx exactly as it is sent
+        assert!(tx.get_data().contains(&signed));
+
+        let signature = data["$sigs"]["identity"].as_str().unwrap();
+        assert!(key.verify(&signed, signature).unwrap());
+    }
+
+    #[test]
+    fn tx_sign_multiple() {
+        let rsa = RSA::new("First").unwrap();
+        let ec = EllipticCurve::new("Second").unwrap();
+
+        let mut tx = Transaction::new(TX);
+        tx.sign("existing", &rsa).unwrap();
+
+        SignatureBuilder::new()
+            .signer("first", &rsa)
+            .signer("second", &ec)
+            .sign(&mut tx)
+            .unwrap();
+
+        let data: Value = serde_json::from_str(tx.get_data()).unwrap();
+        let signed = canonical::to_string(&data["$tx"]);
+
+        let sigs = data["$sigs"].as_object().unwrap();
+        assert_eq!(sigs.len(), 3);
+        assert!(rsa
+            .verify(&signed, sigs["first"].as_str().unwrap())
+            .unwrap());
+        assert!(ec
+            .verify(&signed, sigs["second"].as_str().unwrap())
+            .unwrap());
+    }
+
+    #[test]
+    fn tx_from_value() {
+        let data: Value = serde_json::from_str(TX).unwrap();
+
+        let mut tx = Transaction::from_value(data.clone());
+        assert_eq!(tx.get_data(), canonical::to_string(&data));
+        assert_eq!(tx.as_value().unwrap(), data);
+
+        let key = RSA::new("Test").unwrap();
+        tx.sign("identity", &key).unwrap();
+        assert!(tx.as_value().unwrap()["$sigs"]["identity"].is_string());
+
+        let tx = Transaction::new("not json");
+        assert!(matches!(tx.as_value(), Err(DataError(1000, _))));
+    }
+
+    #[test]
+    fn tx_identities() {
+        let tx = Transaction::builder()
+            .namespace("default")
+            .contract("contract")
+            .input("identity", serde_json::json!({}))
+            .input("other", serde_json::json!({}))
+            .build()
+            .unwrap();
+        assert_eq!(tx.identities().unwrap(), vec!["identity", "other"]);
+
+        let tx = Transaction::new(r#"{"$tx": {"$namespace": "default"}, "$sigs": {}}"#);
+        assert_eq!(tx.identities(), Err(DataError(1006, None)));
+
+        let tx = Transaction::new("{");
+        assert!(matches!(tx.identities(), Err(DataError(1000, _))));
+    }
+
+    #[test]
+    fn tx_validate_signatures_present() {
+        let key = EllipticCurve::new("identity").unwrap();
+
+        let mut tx = Transaction::new(
+            r#"{"$tx": {"$i": {"identity": {}, "unsigned": {}}}, "$sigs": {"other": "sig"}}"#,
+        );
+        tx.sign("identity", &key).unwrap();
+
+        let error = tx.validate_signatures_present().unwrap_err();
+        assert_eq!(error, DataError(1007, None));
+
+        let mismatch = std::error::Error::source(&error)
+            .and_then(|source| source.downcast_ref::<SignatureMismatch>())
+            .unwrap();
+        assert_eq!(mismatch.unsigned(), ["unsigned".to_string()]);
+        assert_eq!(mismatch.unexpected(), ["other".to_string()]);
+
+        let tx = Transaction::new(r#"{"$tx": {"$i": {"identity": {}}}}"#);
+        assert_eq!(tx.validate_signatures_present(), Err(DataError(1002, None)));
+    }
+
+    #[test]
+    fn tx_add_signature() {
+        let first = EllipticCurve::new("first").unwrap();
+        let second = RSA::new("second").unwrap();
+
+        let mut tx = Transaction::builder()
+            .namespace("default")
+            .contract("contract")
+            .input("first", serde_json::json!({}))
+            .input("second", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        tx.sign("first", &first).unwrap();
+        tx.add_signature("second", &second).unwrap();
+
+        assert_eq!(
+            tx.add_signature("first", &first),
+            Err(DataError(1008, None))
+        );
+
+        let mut public_keys = HashMap::new();
+        public_keys.insert("first".to_string(), first.get_pem().unwrap().public);
+        public_keys.insert("second".to_string(), second.get_pem().unwrap().public);
+
+        let verified = tx.verify_signatures(&public_keys).unwrap();
+        assert!(verified["first"]);
+        assert!(verified["second"]);
+    }
+
+    #[test]
+    fn tx_verify_signatures() {
+        let rsa = RSA::new("First").unwrap();
+        let ec = EllipticCurve::new("Second").unwrap();
+        let other = EllipticCurve::new("Other").unwrap();
+
+        let mut tx = Transaction::new(TX);
+        SignatureBuilder::new()
+            .signer("first", &rsa)
+            .signer("second", &ec)
+            .signer("third", &other)
+            .sign(&mut tx)
+            .unwrap();
+
+        let mut public_keys = HashMap::new();
+        public_keys.insert("first".to_string(), rsa.get_pem().unwrap().public);
+        // The wrong key for this identity
+        public_keys.insert("second".to_string(), other.get_pem().unwrap().public);
+
+        let verified = tx.verify_signatures(&public_keys).unwrap();
+        assert_eq!(verified.len(), 3);
+        assert!(verified["first"]);
+        assert!(!verified["second"]);
+        assert!(!verified["third"]);
+
+        let tx = Transaction::new(r#"{"$tx": {}, "$sigs": []}"#);
+        assert!(matches!(
+            tx.verify_signatures(&public_keys),
+            Err(DataError(1002, None))
+        ));
+    }
+
+    #[test]
+    fn tx_sign_canonical() {
+        let key = RSA::new("Test").unwrap();
+
+        let mut tx = Transaction::new(
+            r#"{"$tx": {"$namespace": "default", "$i": {"b": 1.0, "10": 1, "2": 2}}}"#,
+        );
+        tx.sign("identity", &key).unwrap();
+
+        let signed = r#"{"$i":{"2":2,"10":1,"b":1},"$namespace":"default"}"#;
+        assert!(tx.get_data().contains(signed));
+
+        let data: Value = serde_json::from_str(tx.get_data()).unwrap();
+        let signature = data["$sigs"]["identity"].as_str().unwrap();
+        assert!(key.verify(signed, signature).unwrap());
+    }
 }
@@ -0,0 +1,378 @@
+/*
+ * MIT License (MIT)
+ * Copyright (c) 2019 Activeledger
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! The openssl backend, used by default
+
+use openssl::bn::{BigNum, BigNumContext};
+#[cfg(any(test, feature = "test-utils"))]
+use openssl::ec::EcPoint;
+use openssl::ec::{EcGroup, EcKey, PointConversionForm};
+use openssl::ecdsa::EcdsaSig;
+#[cfg(any(test, feature = "test-utils"))]
+use openssl::error::ErrorStack;
+use openssl::hash::MessageDigest;
+use openssl::md::Md;
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Private, Public};
+use openssl::pkey_ctx::PkeyCtx;
+use openssl::rsa::Rsa;
+use openssl::sha;
+use openssl::sign::{Signer, Verifier};
+
+use crate::key::{Curve, HashAlg, KeyError, KeyResult, PrivateKeyFormat};
+
+use super::super::int_def::Pkcs8pemBytes;
+use super::{Backend, KeyKind};
+
+/// The length of `r` and `s` in a raw signature, both curves are 256 bit
+const RAW_COMPONENT_LEN: usize = 32;
+
+/// Key operations using openssl
+pub struct OpenSsl;
+
+impl Backend for OpenSsl {
+    type PrivateKey = PKey<Private>;
+    type PublicKey = PKey<Public>;
+
+    fn generate_rsa(format: PrivateKeyFormat) -> KeyResult<Pkcs8pemBytes> {
+        let rsa = match Rsa::generate(2048) {
+            Ok(rsa) => rsa,
+            Err(_) => return Err(KeyError::GenerationError(1004)),
+        };
+
+        // Get the public key PEM
+        let public = match rsa.public_key_to_pem() {
+            Ok(bytes) => bytes,
+            Err(_) => return Err(KeyError::GenerationError(1006)),
+        };
+
+        // Get the private key PEM
+        let keypair = match PKey::from_rsa(rsa) {
+            Ok(keypair) => keypair,
+            Err(_) => return Err(KeyError::GenerationError(1005)),
+        };
+
+        let private = match OpenSsl::private_pem(&keypair, format) {
+            Ok(bytes) => bytes,
+            Err(_) => return Err(KeyError::GenerationError(1005)),
+        };
+
+        Ok(Pkcs8pemBytes::new(&private, &public))
+    }
+
+    fn generate_ec(curve: Curve) -> KeyResult<Pkcs8pemBytes> {
+        let ec_group = match EcGroup::from_curve_name(nid(curve)) {
+            Ok(group) => group,
+            Err(_) => return Err(KeyError::GenerationError(1000)),
+        };
+
+        let ec_key = match EcKey::generate(&ec_group) {
+            Ok(key) => key,
+            Err(_) => return Err(KeyError::GenerationError(1001)),
+        };
+
+        ec_pkcs8pem(ec_key)
+    }
+
+    #[cfg(any(test, feature = "test-utils"))]
+    fn ec_from_seed(seed: &[u8; 32]) -> KeyResult<Pkcs8pemBytes> {
+        let ec_group = match EcGroup::from_curve_name(Nid::SECP256K1) {
+            Ok(group) => group,
+            Err(_) => return Err(KeyError::GenerationError(1000)),
+        };
+
+        let (private, public) = match seed_components(&ec_group, seed) {
+            Ok(components) => components,
+            Err(_) => return Err(KeyError::GenerationError(1001)),
+        };
+
+        if private.num_bits() == 0 {
+            return Err(KeyError::GenerationError(1007));
+        }
+
+        match EcKey::from_private_components(&ec_group, &private, &public) {
+            Ok(key) => ec_pkcs8pem(key),
+            Err(_) => Err(KeyError::GenerationError(1001)),
+        }
+    }
+
+    fn private_key(pem: &[u8]) -> KeyResult<PKey<Private>> {
+        match PKey::private_key_from_pem(pem) {
+            Ok(key) => Ok(key),
+            Err(_) => Err(KeyError::SigningError(2007)),
+        }
+    }
+
+    fn public_key(pem: &[u8]) -> KeyResult<PKey<Public>> {
+        match PKey::public_key_from_pem(pem) {
+            Ok(key) => Ok(key),
+            Err(_) => Err(KeyError::SigningError(2008)),
+        }
+    }
+
+    fn kind(key: &PKey<Private>) -> Option<KeyKind> {
+        match key.id() {
+            Id::RSA => Some(KeyKind::Rsa),
+            Id::EC => Some(KeyKind::Ec),
+            _ => None,
+        }
+    }
+
+    fn public_half(key: &PKey<Private>) -> KeyResult<PKey<Public>> {
+        let der = match key.public_key_to_der() {
+            Ok(der) => der,
+            Err(_) => return Err(KeyError::SigningError(2008)),
+        };
+
+        match PKey::public_key_from_der(&der) {
+            Ok(key) => Ok(key),
+            Err(_) => Err(KeyError::SigningError(2008)),
+        }
+    }
+
+    fn sign(key: &PKey<Private>, data: &[u8], hash: HashAlg) -> KeyResult<Vec<u8>> {
+        // Create a signer
+        let mut signer = match Signer::new(message_digest(hash), key) {
+            Ok(signer) => signer,
+            Err(_) => return Err(KeyError::SigningError(2000)),
+        };
+
+        // Add data to signer
+        if signer.update(data).is_err() {
+            return Err(KeyError::SigningError(2001));
+        }
+
+        // Get the signature as a vector of bytes
+        match signer.sign_to_vec() {
+            Ok(sig) => Ok(sig),
+            Err(_) => Err(KeyError::SigningError(2002)),
+        }
+    }
+
+    fn sign_prehashed(key: &PKey<Private>, digest: &[u8; 32]) -> KeyResult<Vec<u8>> {
+        let mut ctx = match PkeyCtx::new(key) {
+            Ok(ctx) => ctx,
+            Err(_) => return Err(KeyError::SigningError(2000)),
+        };
+
+        // The digest is signed as SHA-256 so RSA signatures include its DigestInfo
+        if ctx.sign_init().is_err() || ctx.set_signature_md(Md::sha256()).is_err() {
+            return Err(KeyError::SigningError(2000));
+        }
+
+        let mut signature = Vec::new();
+
+        match ctx.sign_to_vec(digest, &mut signature) {
+            Ok(_) => Ok(signature),
+            Err(_) => Err(KeyError::SigningError(2002)),
+        }
+    }
+
+    fn verify(key: &PKey<Public>, data: &[u8], signature: &[u8], hash: HashAlg) -> KeyResult<bool> {
+        // Initialise the OpenSSL verifier
+        let mut verifier = match Verifier::new(message_digest(hash), key) {
+            Ok(verifier) => verifier,
+            Err(_) => return Err(KeyError::SigningError(2004)),
+        };
+
+        // Give it the data
+        if verifier.update(data).is_err() {
+            return Err(KeyError::SigningError(2005));
+        }
+
+        // Run verification on the given signature
+        match verifier.verify(signature) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(KeyError::SigningError(2006)),
+        }
+    }
+
+    fn curve(key: &PKey<Public>) -> KeyResult<Curve> {
+        let key = match key.ec_key() {
+            Ok(key) => key,
+            Err(_) => return Err(KeyError::ImportError(4006)),
+        };
+
+        match key.group().curve_name().and_then(curve_from_nid) {
+            Some(curve) => Ok(curve),
+            None => Err(KeyError::ImportError(4006)),
+        }
+    }
+
+    fn private_der(key: &PKey<Private>) -> KeyResult<Vec<u8>> {
+        match key.private_key_to_der() {
+            Ok(der) => Ok(der),
+            Err(_) => Err(KeyError::StringifyError(3011)),
+        }
+    }
+
+    fn private_pem(key: &PKey<Private>, format: PrivateKeyFormat) -> KeyResult<Vec<u8>> {
+        let private = match (key.id(), format) {
+            (Id::RSA, PrivateKeyFormat::Pkcs1) => {
+                key.rsa().and_then(|rsa| rsa.private_key_to_pem())
+            }
+            _ => key.private_key_to_pem_pkcs8(),
+        };
+
+        match private {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(KeyError::StringifyError(3012)),
+        }
+    }
+
+    fn public_der(key: &PKey<Public>) -> KeyResult<Vec<u8>> {
+        match key.public_key_to_der() {
+            Ok(der) => Ok(der),
+            Err(_) => Err(KeyError::StringifyError(3009)),
+        }
+    }
+
+    fn public_pem(key: &PKey<Public>) -> KeyResult<Vec<u8>> {
+        match key.public_key_to_pem() {
+            Ok(pem) => Ok(pem),
+            Err(_) => Err(KeyError::StringifyError(3009)),
+        }
+    }
+
+    fn compressed_point(key: &PKey<Public>) -> KeyResult<Vec<u8>> {
+        let key = match key.ec_key() {
+            Ok(key) => key,
+            Err(_) => return Err(KeyError::StringifyError(3010)),
+        };
+
+        let mut ctx = match BigNumContext::new() {
+            Ok(ctx) => ctx,
+            Err(_) => return Err(KeyError::StringifyError(3010)),
+        };
+
+        match key
+            .public_key()
+            .to_bytes(key.group(), PointConversionForm::COMPRESSED, &mut ctx)
+        {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(KeyError::StringifyError(3010)),
+        }
+    }
+
+    fn ecdsa_der_to_raw(der: &[u8]) -> KeyResult<Vec<u8>> {
+        let signature = match EcdsaSig::from_der(der) {
+            Ok(signature) => signature,
+            Err(_) => return Err(KeyError::SigningError(2011)),
+        };
+
+        let mut raw = Vec::with_capacity(RAW_COMPONENT_LEN * 2);
+
+        for component in [signature.r(), signature.s()] {
+            match component.to_vec_padded(RAW_COMPONENT_LEN as i32) {
+                Ok(bytes) => raw.extend_from_slice(&bytes),
+                Err(_) => return Err(KeyError::SigningError(2011)),
+            }
+        }
+
+        Ok(raw)
+    }
+
+    fn ecdsa_raw_to_der(raw: &[u8]) -> KeyResult<Vec<u8>> {
+        if raw.len() != RAW_COMPONENT_LEN * 2 {
+            return Err(KeyError::SigningError(2011));
+        }
+
+        let (r, s) = raw.split_at(RAW_COMPONENT_LEN);
+
+        BigNum::from_slice(r)
+            .and_then(|r| Ok((r, BigNum::from_slice(s)?)))
+            .and_then(|(r, s)| EcdsaSig::from_private_components(r, s))
+            .and_then(|signature| signature.to_der())
+            .map_err(|_| KeyError::SigningError(2011))
+    }
+
+    fn sha256(data: &[u8]) -> [u8; 32] {
+        sha::sha256(data)
+    }
+}
+
+/// Get the OpenSSL digest for the hash algorithm
+fn message_digest(hash: HashAlg) -> MessageDigest {
+    match hash {
+        HashAlg::Sha256 => MessageDigest::sha256(),
+        HashAlg::Sha512 => MessageDigest::sha512(),
+    }
+}
+
+fn nid(curve: Curve) -> Nid {
+    match curve {
+        Curve::Secp256k1 => Nid::SECP256K1,
+        Curve::P256 => Nid::X9_62_PRIME256V1,
+    }
+}
+
+fn curve_from_nid(nid: Nid) -> Option<Curve> {
+    match nid {
+        Nid::SECP256K1 => Some(Curve::Secp256k1),
+        Nid::X9_62_PRIME256V1 => Some(Curve::P256),
+        _ => None,
+    }
+}
+
+/// Convert a generated EC key to its private and public PEMs
+fn ec_pkcs8pem(ec_key: EcKey<Private>) -> KeyResult<Pkcs8pemBytes> {
+    let pkey = match PKey::from_ec_key(ec_key) {
+        Ok(pkey) => pkey,
+        Err(_) => return Err(KeyError::GenerationError(1001)),
+    };
+
+    let private = match pkey.private_key_to_pem_pkcs8() {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(KeyError::GenerationError(1002)),
+    };
+
+    // Get the public key PEM
+    let public = match pkey.public_key_to_pem() {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(KeyError::GenerationError(1003)),
+    };
+
+    Ok(Pkcs8pemBytes::new(&private, &public))
+}
+
+/// Derive the private number and public point for a seed
+#[cfg(any(test, feature = "test-utils"))]
+fn seed_components(ec_group: &EcGroup, seed: &[u8]) -> Result<(BigNum, EcPoint), ErrorStack> {
+    let mut ctx = BigNumContext::new()?;
+
+    let mut order = BigNum::new()?;
+    ec_group.order(&mut order, &mut ctx)?;
+
+    // Reduce the seed so it is a valid private key for the curve
+    let seed = BigNum::from_slice(seed)?;
+    let mut private = BigNum::new()?;
+    private.nnmod(&seed, &order, &mut ctx)?;
+
+    // mul_generator2 needs openssl 0.10.79, the context isn't shared so the
+    // older method is safe to use here
+    let mut public = EcPoint::new(ec_group)?;
+    #[allow(deprecated)]
+    public.mul_generator(ec_group, &private, &ctx)?;
+
+    Ok((private, public))
+}
@@ -0,0 +1,410 @@
+/*
+ * MIT License (MIT)
+ * Copyright (c) 2019 Activeledger
+ *
+ * Permission is hereby granted, free of charge, to any person obtaining a copy
+ * of this software and associated documentation files (the "Software"), to deal
+ * in the Software without restriction, including without limitation the rights
+ * to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ * copies of the Software, and to permit persons to whom the Software is
+ * furnished to do so, subject to the following conditions:
+ *
+ * The above copyright notice and this permission notice shall be included in all
+ * copies or substantial portions of the Software.
+ *
+ * THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ * IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ * FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ * AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ * LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ * OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ * SOFTWARE.
+ */
+
+//! The pure Rust backend, used when the `rustcrypto` feature is enabled
+
+use k256::elliptic_curve::SecretKey;
+#[cfg(any(test, feature = "test-utils"))]
+use k256::elliptic_curve::{ops::Reduce, NonZeroScalar};
+use k256::pkcs8::der::{Decode, Encode};
+use k256::pkcs8::{AssociatedOid, DecodePrivateKey, DecodePublicKey, EncodePrivateKey};
+use k256::pkcs8::{EncodePublicKey, LineEnding, ObjectIdentifier};
+use rand_core::OsRng;
+use rsa::pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey};
+use rsa::signature::hazmat::{PrehashSigner, PrehashVerifier};
+use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use sec1::{EcParameters, EcPrivateKey};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::key::{Curve, HashAlg, KeyError, KeyResult, PrivateKeyFormat};
+
+use super::super::int_def::Pkcs8pemBytes;
+use super::{Backend, KeyKind};
+
+/// The length of `r` and `s` in a raw signature, both curves are 256 bit
+const RAW_COMPONENT_LEN: usize = 32;
+
+/// Key operations using the `rsa`, `k256` and `p256` crates
+pub struct RustCrypto;
+
+/// A parsed private key
+#[derive(Clone)]
+pub enum PrivateKey {
+    Rsa(Box<RsaPrivateKey>),
+    Secp256k1(k256::ecdsa::SigningKey),
+    P256(p256::ecdsa::SigningKey),
+}
+
+/// A parsed public key
+#[derive(Clone)]
+pub enum PublicKey {
+    Rsa(RsaPublicKey),
+    Secp256k1(k256::ecdsa::VerifyingKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl Backend for RustCrypto {
+    type PrivateKey = PrivateKey;
+    type PublicKey = PublicKey;
+
+    fn generate_rsa(format: PrivateKeyFormat) -> KeyResult<Pkcs8pemBytes> {
+        let key = match RsaPrivateKey::new(&mut OsRng, 2048) {
+            Ok(key) => PrivateKey::Rsa(Box::new(key)),
+            Err(_) => return Err(KeyError::GenerationError(1004)),
+        };
+
+        let public =
+            match RustCrypto::public_half(&key).and_then(|key| RustCrypto::public_pem(&key)) {
+                Ok(bytes) => bytes,
+                Err(_) => return Err(KeyError::GenerationError(1006)),
+            };
+
+        let private = match RustCrypto::private_pem(&key, format) {
+            Ok(bytes) => bytes,
+            Err(_) => return Err(KeyError::GenerationError(1005)),
+        };
+
+        Ok(Pkcs8pemBytes::new(&private, &public))
+    }
+
+    fn generate_ec(curve: Curve) -> KeyResult<Pkcs8pemBytes> {
+        let key = match curve {
+            Curve::Secp256k1 => PrivateKey::Secp256k1(k256::ecdsa::SigningKey::random(&mut OsRng)),
+            Curve::P256 => PrivateKey::P256(p256::ecdsa::SigningKey::random(&mut OsRng)),
+        };
+
+        ec_pkcs8pem(&key)
+    }
+
+    #[cfg(any(test, feature = "test-utils"))]
+    fn ec_from_seed(seed: &[u8; 32]) -> KeyResult<Pkcs8pemBytes> {
+        // Reduce the seed so it is a valid private key for the curve
+        let scalar = <k256::Scalar as Reduce<k256::U256>>::reduce_bytes(&(*seed).into());
+
+        let scalar: Option<NonZeroScalar<k256::Secp256k1>> = NonZeroScalar::new(scalar).into();
+
+        match scalar {
+            Some(scalar) => ec_pkcs8pem(&PrivateKey::Secp256k1(scalar.into())),
+            None => Err(KeyError::GenerationError(1007)),
+        }
+    }
+
+    fn private_key(pem: &[u8]) -> KeyResult<PrivateKey> {
+        let (label, der) = match decode_pem(pem) {
+            Some(decoded) => decoded,
+            None => return Err(KeyError::SigningError(2007)),
+        };
+
+        let key = match label.as_str() {
+            "RSA PRIVATE KEY" => RsaPrivateKey::from_pkcs1_der(&der)
+                .ok()
+                .map(|key| PrivateKey::Rsa(Box::new(key))),
+            // PKCS#8 holds the algorithm and curve, each parser checks they match
+            "PRIVATE KEY" => RsaPrivateKey::from_pkcs8_der(&der)
+                .map(|key| PrivateKey::Rsa(Box::new(key)))
+                .or_else(|_| {
+                    k256::ecdsa::SigningKey::from_pkcs8_der(&der).map(PrivateKey::Secp256k1)
+                })
+                .or_else(|_| p256::ecdsa::SigningKey::from_pkcs8_der(&der).map(PrivateKey::P256))
+                .ok(),
+            "EC PRIVATE KEY" => sec1_private_key(&der),
+            _ => None,
+        };
+
+        match key {
+            Some(key) => Ok(key),
+            None => Err(KeyError::SigningError(2007)),
+        }
+    }
+
+    fn public_key(pem: &[u8]) -> KeyResult<PublicKey> {
+        let der = match decode_pem(pem) {
+            Some((label, der)) if label == "PUBLIC KEY" => der,
+            _ => return Err(KeyError::SigningError(2008)),
+        };
+
+        RsaPublicKey::from_public_key_der(&der)
+            .map(PublicKey::Rsa)
+            .or_else(|_| {
+                k256::ecdsa::VerifyingKey::from_public_key_der(&der).map(PublicKey::Secp256k1)
+            })
+            .or_else(|_| p256::ecdsa::VerifyingKey::from_public_key_der(&der).map(PublicKey::P256))
+            .map_err(|_| KeyError::SigningError(2008))
+    }
+
+    fn kind(key: &PrivateKey) -> Option<KeyKind> {
+        match key {
+            PrivateKey::Rsa(_) => Some(KeyKind::Rsa),
+            PrivateKey::Secp256k1(_) | PrivateKey::P256(_) => Some(KeyKind::Ec),
+        }
+    }
+
+    fn public_half(key: &PrivateKey) -> KeyResult<PublicKey> {
+        Ok(match key {
+            PrivateKey::Rsa(key) => PublicKey::Rsa(key.to_public_key()),
+            PrivateKey::Secp256k1(key) => PublicKey::Secp256k1(*key.verifying_key()),
+            PrivateKey::P256(key) => PublicKey::P256(*key.verifying_key()),
+        })
+    }
+
+    fn sign(key: &PrivateKey, data: &[u8], hash: HashAlg) -> KeyResult<Vec<u8>> {
+        sign_digest(key, &digest(data, hash), hash)
+    }
+
+    fn sign_prehashed(key: &PrivateKey, digest: &[u8; 32]) -> KeyResult<Vec<u8>> {
+        sign_digest(key, digest, HashAlg::Sha256)
+    }
+
+    fn verify(key: &PublicKey, data: &[u8], signature: &[u8], hash: HashAlg) -> KeyResult<bool> {
+        let digest = digest(data, hash);
+
+        match key {
+            PublicKey::Rsa(key) => Ok(key.verify(pkcs1v15(hash), &digest, signature).is_ok()),
+            PublicKey::Secp256k1(key) => {
+                let signature = match k256::ecdsa::Signature::from_der(signature) {
+                    Ok(signature) => signature,
+                    Err(_) => return Err(KeyError::SigningError(2006)),
+                };
+
+                // k256 rejects high S values, which openssl and other signers produce
+                let signature = signature.normalize_s().unwrap_or(signature);
+
+                Ok(key.verify_prehash(&digest, &signature).is_ok())
+            }
+            PublicKey::P256(key) => {
+                let signature = match p256::ecdsa::Signature::from_der(signature) {
+                    Ok(signature) => signature,
+                    Err(_) => return Err(KeyError::SigningError(2006)),
+                };
+
+                Ok(key.verify_prehash(&digest, &signature).is_ok())
+            }
+        }
+    }
+
+    fn curve(key: &PublicKey) -> KeyResult<Curve> {
+        match key {
+            PublicKey::Secp256k1(_) => Ok(Curve::Secp256k1),
+            PublicKey::P256(_) => Ok(Curve::P256),
+            PublicKey::Rsa(_) => Err(KeyError::ImportError(4006)),
+        }
+    }
+
+    fn private_der(key: &PrivateKey) -> KeyResult<Vec<u8>> {
+        let der = match key {
+            PrivateKey::Rsa(key) => key.to_pkcs1_der().map(|der| der.as_bytes().to_vec()).ok(),
+            PrivateKey::Secp256k1(key) => {
+                let point = key.verifying_key().to_encoded_point(false);
+                sec1_der(&key.to_bytes(), k256::Secp256k1::OID, point.as_bytes())
+            }
+            PrivateKey::P256(key) => {
+                let point = key.verifying_key().to_encoded_point(false);
+                sec1_der(&key.to_bytes(), p256::NistP256::OID, point.as_bytes())
+            }
+        };
+
+        match der {
+            Some(der) => Ok(der),
+            None => Err(KeyError::StringifyError(3011)),
+        }
+    }
+
+    fn private_pem(key: &PrivateKey, format: PrivateKeyFormat) -> KeyResult<Vec<u8>> {
+        let pem = match (key, format) {
+            (PrivateKey::Rsa(key), PrivateKeyFormat::Pkcs1) => {
+                key.to_pkcs1_pem(LineEnding::LF).ok()
+            }
+            (PrivateKey::Rsa(key), PrivateKeyFormat::Pkcs8) => {
+                key.to_pkcs8_pem(LineEnding::LF).ok()
+            }
+            (PrivateKey::Secp256k1(key), _) => key.to_pkcs8_pem(LineEnding::LF).ok(),
+            (PrivateKey::P256(key), _) => key.to_pkcs8_pem(LineEnding::LF).ok(),
+        };
+
+        match pem {
+            Some(pem) => Ok(pem.as_bytes().to_vec()),
+            None => Err(KeyError::StringifyError(3012)),
+        }
+    }
+
+    fn public_der(key: &PublicKey) -> KeyResult<Vec<u8>> {
+        let der = match key {
+            PublicKey::Rsa(key) => key.to_public_key_der(),
+            PublicKey::Secp256k1(key) => key.to_public_key_der(),
+            PublicKey::P256(key) => key.to_public_key_der(),
+        };
+
+        match der {
+            Ok(der) => Ok(der.into_vec()),
+            Err(_) => Err(KeyError::StringifyError(3009)),
+        }
+    }
+
+    fn public_pem(key: &PublicKey) -> KeyResult<Vec<u8>> {
+        let pem = match key {
+            PublicKey::Rsa(key) => key.to_public_key_pem(LineEnding::LF),
+            PublicKey::Secp256k1(key) => key.to_public_key_pem(LineEnding::LF),
+            PublicKey::P256(key) => key.to_public_key_pem(LineEnding::LF),
+        };
+
+        match pem {
+            Ok(pem) => Ok(pem.into_bytes()),
+            Err(_) => Err(KeyError::StringifyError(3009)),
+        }
+    }
+
+    fn compressed_point(key: &PublicKey) -> KeyResult<Vec<u8>> {
+        match key {
+            PublicKey::Secp256k1(key) => Ok(key.to_encoded_point(true).as_bytes().to_vec()),
+            PublicKey::P256(key) => Ok(key.to_encoded_point(true).as_bytes().to_vec()),
+            PublicKey::Rsa(_) => Err(KeyError::StringifyError(3010)),
+        }
+    }
+
+    fn ecdsa_der_to_raw(der: &[u8]) -> KeyResult<Vec<u8>> {
+        // The secp256k1 order is larger than the P-256 order, so its signature
+        // type holds signatures from either curve
+        match k256::ecdsa::Signature::from_der(der) {
+            Ok(signature) => Ok(signature.to_bytes().to_vec()),
+            Err(_) => Err(KeyError::SigningError(2011)),
+        }
+    }
+
+    fn ecdsa_raw_to_der(raw: &[u8]) -> KeyResult<Vec<u8>> {
+        if raw.len() != RAW_COMPONENT_LEN * 2 {
+            return Err(KeyError::SigningError(2011));
+        }
+
+        match k256::ecdsa::Signature::from_slice(raw) {
+            Ok(signature) => Ok(signature.to_der().as_bytes().to_vec()),
+            Err(_) => Err(KeyError::SigningError(2011)),
+        }
+    }
+
+    fn sha256(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+}
+
+/// Sign a digest made with the given hash algorithm
+///
+/// ECDSA truncates digests longer than the curve, as openssl does.
+fn sign_digest(key: &PrivateKey, digest: &[u8], hash: HashAlg) -> KeyResult<Vec<u8>> {
+    let signature = match key {
+        PrivateKey::Rsa(key) => key.sign_with_rng(&mut OsRng, pkcs1v15(hash), digest).ok(),
+        PrivateKey::Secp256k1(key) => {
+            PrehashSigner::<k256::ecdsa::Signature>::sign_prehash(key, digest)
+                .map(|signature| signature.to_der().as_bytes().to_vec())
+                .ok()
+        }
+        PrivateKey::P256(key) => PrehashSigner::<p256::ecdsa::Signature>::sign_prehash(key, digest)
+            .map(|signature| signature.to_der().as_bytes().to_vec())
+            .ok(),
+    };
+
+    match signature {
+        Some(signature) => Ok(signature),
+        None => Err(KeyError::SigningError(2002)),
+    }
+}
+
+/// Get the digest of the data
+fn digest(data: &[u8], hash: HashAlg) -> Vec<u8> {
+    match hash {
+        HashAlg::Sha256 => Sha256::digest(data).to_vec(),
+        HashAlg::Sha512 => Sha512::digest(data).to_vec(),
+    }
+}
+
+/// Get the PKCS#1 v1.5 padding for the hash algorithm
+fn pkcs1v15(hash: HashAlg) -> Pkcs1v15Sign {
+    match hash {
+        HashAlg::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+        HashAlg::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+    }
+}
+
+/// Decode a PEM, returning its label and DER
+///
+/// Like openssl, text before the PEM and the length of its lines are ignored.
+fn decode_pem(pem: &[u8]) -> Option<(String, Vec<u8>)> {
+    let pem = std::str::from_utf8(pem).ok()?;
+
+    let start = pem.find("-----BEGIN ")? + "-----BEGIN ".len();
+    let label_len = pem[start..].find("-----")?;
+    let label = &pem[start..start + label_len];
+
+    let body = &pem[start + label_len + "-----".len()..];
+    let end = body.find(&format!("-----END {}-----", label))?;
+
+    let encoded: String = body[..end].split_whitespace().collect();
+
+    match base64::decode(&encoded) {
+        Ok(der) => Some((label.to_string(), der)),
+        Err(_) => None,
+    }
+}
+
+/// Parse a SEC1 `EC PRIVATE KEY`, the curve is read from its parameters
+fn sec1_private_key(der: &[u8]) -> Option<PrivateKey> {
+    let key = EcPrivateKey::from_der(der).ok()?;
+
+    match key
+        .parameters
+        .and_then(|parameters| parameters.named_curve())?
+    {
+        oid if oid == k256::Secp256k1::OID => SecretKey::<k256::Secp256k1>::from_sec1_der(der)
+            .ok()
+            .map(|key| PrivateKey::Secp256k1(key.into())),
+        oid if oid == p256::NistP256::OID => SecretKey::<p256::NistP256>::from_sec1_der(der)
+            .ok()
+            .map(|key| PrivateKey::P256(key.into())),
+        _ => None,
+    }
+}
+
+/// Encode a SEC1 private key with its curve and public point, as openssl does
+fn sec1_der(private: &[u8], curve: ObjectIdentifier, public: &[u8]) -> Option<Vec<u8>> {
+    EcPrivateKey {
+        private_key: private,
+        parameters: Some(EcParameters::NamedCurve(curve)),
+        public_key: Some(public),
+    }
+    .to_der()
+    .ok()
+}
+
+/// Get the private and public PEMs of a generated EC key
+fn ec_pkcs8pem(key: &PrivateKey) -> KeyResult<Pkcs8pemBytes> {
+    let private = match RustCrypto::private_pem(key, PrivateKeyFormat::Pkcs8) {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(KeyError::GenerationError(1002)),
+    };
+
+    let public = match RustCrypto::public_half(key).and_then(|key| RustCrypto::public_pem(&key)) {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(KeyError::GenerationError(1003)),
+    };
+
+    Ok(Pkcs8pemBytes::new(&private, &public))
+}
@@ -23,8 +23,8 @@
 
 //! # EC Key
 //!
-//! This module is used to generate an elliptic curve key, this is generated using
-//! the secp256k1 alogrithm.
+//! This module is used to generate an elliptic curve key, by default this is generated using
+//! the secp256k1 alogrithm. Keys using the P-256 (prime256v1) curve are also supported.
 //!
 //! ## Examples
 //! ### Create a new key
@@ -33,6 +33,12 @@
 //! let ec_key = EllipticCurve::new("key name").unwrap();
 //! ```
 //!
+//! ### Create a new key using the P-256 curve
+//! ```
+//! # use activeledger::key::{Curve, EllipticCurve};
+//! let ec_key = EllipticCurve::new_with_curve("key name", Curve::P256).unwrap();
+//! ```
+//!
 //! ### Use an existing PEM to create a key object
 //! ```
 //! # use activeledger::key::EllipticCurve;
@@ -66,26 +72,54 @@
 //! let pem = ec_key.get_pem();
 //! ```
 
-extern crate openssl;
-
+use std::fmt;
 use std::str;
 
-use openssl::ec::{EcGroup, EcKey};
-use openssl::nid::Nid;
+#[cfg(feature = "openssl")]
 use openssl::pkey::{PKey, Private};
 
-use crate::key::Pkcs8pem;
+use crate::key::{HashAlg, Key, Pkcs8pem, Signer, VerifyOutcome};
 
 use super::error::{KeyError, KeyResult};
 
-#[path = "int_def.rs"]
-mod int_def;
-use int_def::{Pkcs8pemBytes, Signing};
+use super::backend::{Active, Backend, KeyKind, PrivateKey};
+use super::int_def::{self, ParsedKeys, Pkcs8pemBytes, Signing};
+
+/// The elliptic curves supported by EllipticCurve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Curve {
+    /// secp256k1, the curve used by default
+    #[default]
+    Secp256k1,
+    /// NIST P-256, also known as prime256v1 or secp256r1
+    P256,
+}
+
+/// How the public key point is encoded in a public key PEM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PointFormat {
+    /// Both coordinates of the point, used by default
+    #[default]
+    Uncompressed,
+    /// Only the x coordinate and the sign of y, needed by some blockchain tools
+    Compressed,
+}
+
+/// How ECDSA signatures are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SignatureFormat {
+    /// A DER encoded `ECDSA-Sig-Value`, used by default and by Activeledger
+    #[default]
+    Der,
+    /// The 32 byte `r` and `s` values joined together, as used by Ethereum style verifiers
+    Raw,
+}
 
 #[derive(Clone)]
 pub struct EllipticCurve {
     pub name: String,
     pkcs8pem: Pkcs8pemBytes,
+    parsed: ParsedKeys,
 }
 
 // Public functions
@@ -98,16 +132,72 @@ impl EllipticCurve {
     /// let ec_key = EllipticCurve::new("key name").unwrap();
     /// ```
     pub fn new(name: &str) -> KeyResult<EllipticCurve> {
-        let pkcs8pem = EllipticCurve::generate()?;
+        EllipticCurve::new_with_curve(name, Curve::default())
+    }
+
+    /// Generate a new EC Key using the given curve
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::{Curve, EllipticCurve};
+    /// let ec_key = EllipticCurve::new_with_curve("key name", Curve::P256).unwrap();
+    /// ```
+    pub fn new_with_curve(name: &str, curve: Curve) -> KeyResult<EllipticCurve> {
+        let pkcs8pem = EllipticCurve::generate(curve)?;
 
         Ok(EllipticCurve {
             name: String::from(name),
             pkcs8pem,
+            parsed: ParsedKeys::default(),
+        })
+    }
+
+    /// Derive a secp256k1 key from a seed, for reproducible test fixtures
+    ///
+    /// The seed is read as a big endian number and reduced by the curve order to
+    /// give the private key, so the same seed always gives the same key.
+    /// Requires the `test-utils` feature.
+    ///
+    /// **This is not secure.** Anyone who knows the seed knows the private key,
+    /// never use keys created this way outside of tests.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let ec_key = EllipticCurve::from_seed("key name", &[7; 32]).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns GenerationError 1007 if the seed reduces to zero, which is not a valid key.
+    #[cfg(any(test, feature = "test-utils"))]
+    pub fn from_seed(name: &str, seed: &[u8; 32]) -> KeyResult<EllipticCurve> {
+        Ok(EllipticCurve {
+            name: String::from(name),
+            pkcs8pem: Active::ec_from_seed(seed)?,
+            parsed: ParsedKeys::default(),
         })
     }
 
+    /// Generate a key for each of the given names using the default curve
+    ///
+    /// The keys are returned in the same order as the names. Enable the `rayon`
+    /// feature to generate the keys in parallel.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let keys = EllipticCurve::generate_batch(&["first", "second"]);
+    ///
+    /// assert_eq!(keys[1].as_ref().unwrap().name, "second");
+    /// ```
+    pub fn generate_batch(names: &[&str]) -> Vec<KeyResult<EllipticCurve>> {
+        int_def::generate_batch(names, EllipticCurve::new)
+    }
+
     /// Create a new key using a given PEM
     ///
+    /// The curve is read from the PEM, see `curve()`.
+    ///
     /// # Example
     /// ```
     /// # use activeledger::key::EllipticCurve;
@@ -125,9 +215,50 @@ impl EllipticCurve {
         EllipticCurve {
             name: String::from(name),
             pkcs8pem,
+            parsed: ParsedKeys::default(),
         }
     }
 
+    /// Create a new key using a given PEM with a passphrase protected private key
+    ///
+    /// The private key is decrypted when the key is created. Requires the `openssl` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// # use activeledger::key::Pkcs8pem;
+    /// # let pem: Pkcs8pem = Pkcs8pem::new("", "");
+    /// let ec_key = EllipticCurve::create_from_encrypted_pem("NAME", &pem, "passphrase");
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns ImportError 4003 if the private key cannot be decrypted with the given passphrase.
+    #[cfg(feature = "openssl")]
+    pub fn create_from_encrypted_pem(
+        name: &str,
+        pem: &Pkcs8pem,
+        passphrase: &str,
+    ) -> KeyResult<EllipticCurve> {
+        let pkey = match PKey::private_key_from_pem_passphrase(
+            pem.private.as_bytes(),
+            passphrase.as_bytes(),
+        ) {
+            Ok(pkey) => pkey,
+            Err(_) => return Err(KeyError::ImportError(4003)),
+        };
+
+        let private = match pkey.private_key_to_pem_pkcs8() {
+            Ok(bytes) => bytes,
+            Err(_) => return Err(KeyError::ImportError(4004)),
+        };
+
+        Ok(EllipticCurve {
+            name: String::from(name),
+            pkcs8pem: Pkcs8pemBytes::new(&private, pem.public.as_bytes()),
+            parsed: ParsedKeys::default(),
+        })
+    }
+
     /// Sign the given data
     ///
     /// # Example
@@ -138,13 +269,140 @@ impl EllipticCurve {
     /// let signature = ec.sign("Data to sign").unwrap();
     /// ```
     pub fn sign(&self, data: &str) -> KeyResult<String> {
+        self.sign_with_hash(data, HashAlg::default())
+    }
+
+    /// Sign the given data using the given hash algorithm
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::{HashAlg, EllipticCurve};
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let signature = ec.sign_with_hash("Data to sign", HashAlg::Sha512).unwrap();
+    /// ```
+    pub fn sign_with_hash(&self, data: &str, hash: HashAlg) -> KeyResult<String> {
         let keypair = self.get_keypair()?;
 
-        let signature = Signing::sign(&keypair, &data)?;
+        let signature = Signing::sign(keypair, data, hash)?;
 
         Ok(signature)
     }
 
+    /// Sign the given data and return the raw signature bytes
+    ///
+    /// `sign` returns the same signature base64 encoded.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let signature: Vec<u8> = ec.sign_to_bytes("Data to sign").unwrap();
+    /// ```
+    pub fn sign_to_bytes(&self, data: &str) -> KeyResult<Vec<u8>> {
+        let keypair = self.get_keypair()?;
+
+        Signing::sign_to_bytes(keypair, data.as_bytes(), HashAlg::default())
+    }
+
+    /// Sign a SHA-256 digest of the data instead of the data itself
+    ///
+    /// Useful when the digest has already been computed, for example while
+    /// streaming a large file. The digest must be SHA-256, the algorithm used by
+    /// `sign()`, and the signature can be verified against the original data.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// # use sha2::{Digest, Sha256};
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let digest = Sha256::digest(b"Data to sign");
+    /// let signature = ec.sign_prehashed(&digest).unwrap();
+    ///
+    /// assert!(ec.verify("Data to sign", &signature).unwrap());
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns SigningError 2010 if the digest is not 32 bytes long.
+    pub fn sign_prehashed(&self, digest: &[u8]) -> KeyResult<String> {
+        let keypair = self.get_keypair()?;
+
+        Signing::sign_prehashed(keypair, digest)
+    }
+
+    /// Sign the given data, encoding the signature in the given format
+    ///
+    /// The signature is base64 encoded. Activeledger expects DER signatures,
+    /// raw signatures are for other verifiers.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::{EllipticCurve, SignatureFormat};
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let signature = ec.sign_with_format("Data to sign", SignatureFormat::Raw).unwrap();
+    ///
+    /// let verify = ec
+    ///     .verify_with_format("Data to sign", &signature, SignatureFormat::Raw)
+    ///     .unwrap();
+    /// ```
+    pub fn sign_with_format(&self, data: &str, format: SignatureFormat) -> KeyResult<String> {
+        let signature = self.sign_to_bytes(data)?;
+
+        match format {
+            SignatureFormat::Der => Ok(base64::encode(&signature)),
+            SignatureFormat::Raw => Ok(base64::encode(&EllipticCurve::der_to_raw(&signature)?)),
+        }
+    }
+
+    /// Verify a signature encoded in the given format against some data
+    ///
+    /// See `sign_with_format`.
+    pub fn verify_with_format(
+        &self,
+        data: &str,
+        signature: &str,
+        format: SignatureFormat,
+    ) -> KeyResult<bool> {
+        let signature = Signing::decode_signature(signature)?;
+
+        let signature = match format {
+            SignatureFormat::Der => signature,
+            SignatureFormat::Raw => EllipticCurve::raw_to_der(&signature)?,
+        };
+
+        self.verify_bytes_sig(data, &signature)
+    }
+
+    /// Convert a DER encoded ECDSA signature to the raw `r || s` form
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    /// let der = ec.sign_to_bytes("Data to sign").unwrap();
+    ///
+    /// let raw = EllipticCurve::der_to_raw(&der).unwrap();
+    /// assert_eq!(raw.len(), 64);
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns SigningError 2011 if the signature is not a valid DER signature
+    /// for a 256 bit curve.
+    pub fn der_to_raw(der: &[u8]) -> KeyResult<Vec<u8>> {
+        Active::ecdsa_der_to_raw(der)
+    }
+
+    /// Convert a raw `r || s` ECDSA signature to DER
+    ///
+    /// ## Errors
+    /// Returns SigningError 2011 if the signature is not 64 bytes long.
+    pub fn raw_to_der(raw: &[u8]) -> KeyResult<Vec<u8>> {
+        Active::ecdsa_raw_to_der(raw)
+    }
+
     /// Verify a signature against some data
     ///
     /// # Example
@@ -158,11 +416,243 @@ impl EllipticCurve {
     /// let verify: bool = ec.verify(&data_to_sign, &signature).unwrap();
     /// ```
     pub fn verify(&self, data: &str, signature: &str) -> KeyResult<bool> {
+        self.verify_with_hash(data, signature, HashAlg::default())
+    }
+
+    /// Verify a signature created using the given hash algorithm
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::{HashAlg, EllipticCurve};
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let signature = ec.sign_with_hash("Data to sign", HashAlg::Sha512).unwrap();
+    ///
+    /// let verify = ec.verify_with_hash("Data to sign", &signature, HashAlg::Sha512).unwrap();
+    /// ```
+    pub fn verify_with_hash(&self, data: &str, signature: &str, hash: HashAlg) -> KeyResult<bool> {
+        let signature = Signing::decode_signature(signature)?;
+
+        self.verify_bytes_with_hash(data, &signature, hash)
+    }
+
+    /// Verify a raw signature, as returned by `sign_to_bytes`, against some data
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let signature = ec.sign_to_bytes("Data to sign").unwrap();
+    ///
+    /// let verify = ec.verify_bytes_sig("Data to sign", &signature).unwrap();
+    /// ```
+    pub fn verify_bytes_sig(&self, data: &str, signature: &[u8]) -> KeyResult<bool> {
+        self.verify_bytes_with_hash(data, signature, HashAlg::default())
+    }
+
+    /// Verify a signature and report why verification failed
+    ///
+    /// Unlike `verify`, a signature that can not be decoded is reported as
+    /// `VerifyOutcome::MalformedSignature` instead of an error, so it can be
+    /// told apart from a signature that does not match the data.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::{EllipticCurve, VerifyOutcome};
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let signature = ec.sign("Data to sign").unwrap();
+    ///
+    /// let outcome = ec.verify_detailed("Other data", &signature).unwrap();
+    /// assert_eq!(outcome, VerifyOutcome::Invalid);
+    /// ```
+    pub fn verify_detailed(&self, data: &str, signature: &str) -> KeyResult<VerifyOutcome> {
+        Signing::outcome(self.verify(data, signature))
+    }
+
+    /// Check if the key has a valid private key
+    ///
+    /// Keys created from only a public PEM can verify signatures but not sign,
+    /// signing without a private key returns SigningError 2009.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::{Pkcs8pem, EllipticCurve};
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    /// assert!(ec.has_private_key());
+    ///
+    /// let public = ec.get_pem().unwrap().public;
+    /// let public_only = EllipticCurve::create_from_pem("keyname", &Pkcs8pem::new("", &public));
+    /// assert!(!public_only.has_private_key());
+    /// ```
+    pub fn has_private_key(&self) -> bool {
+        self.get_keypair().is_ok()
+    }
+
+    /// Check that the keys PEMs are valid and belong to the same keypair
+    ///
+    /// Keys created from a PEM are not checked when they are created,
+    /// this can be used to catch an invalid PEM before it is used.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// ec.validate().unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns ValidationError 6000 if the private PEM is invalid, 6001 if the public PEM
+    /// is invalid, and 6002 if the private and public keys do not match.
+    pub fn validate(&self) -> KeyResult<()> {
+        let keypair = match self.get_keypair() {
+            Ok(keypair) => keypair,
+            Err(_) => return Err(KeyError::ValidationError(6000)),
+        };
+
+        Signing::validate(keypair, &self.pkcs8pem.public)
+    }
+
+    /// Get the curve used by the key
+    ///
+    /// The curve is read from the private key, or the public key PEM when there is no
+    /// private key.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::{Curve, EllipticCurve};
+    /// let ec = EllipticCurve::new_with_curve("keyname", Curve::P256).unwrap();
+    ///
+    /// assert_eq!(ec.curve().unwrap(), Curve::P256);
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns SigningError 2007 if the private PEM cannot be parsed, SigningError 2008
+    /// if the public PEM cannot be parsed and ImportError 4006 if the PEM uses a curve
+    /// that is not supported.
+    pub fn curve(&self) -> KeyResult<Curve> {
+        let verifier = self
+            .parsed
+            .verifier(&self.pkcs8pem.public, || self.parse_keypair())?;
+
+        Active::curve(verifier)
+    }
+
+    /// Get a fingerprint of the public key
+    ///
+    /// The fingerprint is the SHA-256 hash of the DER encoded public key as
+    /// colon separated hex, it can be logged without exposing the key.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let fingerprint = ec.get_fingerprint().unwrap();
+    /// ```
+    pub fn get_fingerprint(&self) -> KeyResult<String> {
+        int_def::fingerprint(&self.pkcs8pem.public)
+    }
+
+    /// Get the public key as a hex encoded compressed point
+    ///
+    /// The compressed SEC1 form is 33 bytes, a prefix byte followed by the x
+    /// coordinate, as used by many tools working with secp256k1 keys.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let compressed = ec.get_compressed_public().unwrap();
+    /// assert_eq!(compressed.len(), 66);
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns StringifyError 3008 if the public PEM is invalid and 3010 if the
+    /// point can not be converted.
+    pub fn get_compressed_public(&self) -> KeyResult<String> {
+        let bytes = self.compressed_point()?;
+
+        Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    /// Get the PEMs with the public key point in the given format
+    ///
+    /// `get_pem()` gives the uncompressed format. The private PEM is the same
+    /// for both formats.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::{EllipticCurve, PointFormat};
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let pem = ec.get_pem_with_point_format(PointFormat::Compressed).unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns StringifyError 3008 if the public PEM is invalid and 3009 or 3010
+    /// if it can't be converted.
+    pub fn get_pem_with_point_format(&self, format: PointFormat) -> KeyResult<Pkcs8pem> {
+        let mut pem = self.get_pem()?;
+
+        if format == PointFormat::Compressed {
+            pem.public = self.compressed_public_pem()?;
+        }
+
+        Ok(pem)
+    }
+
+    /// Get the keys in DER form, the private key first
+    ///
+    /// The private key is in the traditional format for the key type and the
+    /// public key is a DER encoded SubjectPublicKeyInfo.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let (private_der, public_der) = ec.get_der().unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns SigningError 2007 if the private PEM is invalid or 2009 if there is no
+    /// private key. StringifyError 3008 is returned if the public PEM is invalid and
+    /// 3009 or 3011 if the conversion fails.
+    pub fn get_der(&self) -> KeyResult<(Vec<u8>, Vec<u8>)> {
         let keypair = self.get_keypair()?;
 
-        let verified = Signing::verify(&keypair, &data, &signature)?;
+        int_def::to_der(keypair, &self.pkcs8pem.public)
+    }
+
+    /// Get the key as an openssl `PKey` for operations this crate doesn't provide
+    ///
+    /// The returned type comes from the openssl crate, so an openssl update that
+    /// changes it may break code using this method even without a major release
+    /// of this crate. Requires the `openssl` feature.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::EllipticCurve;
+    /// let ec = EllipticCurve::new("keyname").unwrap();
+    ///
+    /// let pkey = ec.to_pkey().unwrap();
+    /// ```
+    ///
+    /// ## Errors
+    /// Returns SigningError 2007 if the private PEM is invalid or 2009 if there is no
+    /// private key.
+    #[cfg(feature = "openssl")]
+    pub fn to_pkey(&self) -> KeyResult<PKey<Private>> {
+        let private_der = Active::private_der(self.get_keypair()?)?;
 
-        Ok(verified)
+        match PKey::private_key_from_der(&private_der) {
+            Ok(pkey) => Ok(pkey),
+            Err(_) => Err(KeyError::SigningError(2007)),
+        }
     }
 
     /// Get a keys PEM as string values
@@ -195,52 +685,127 @@ impl EllipticCurve {
 
 // Private functions
 impl EllipticCurve {
-    /// Generate the PEM
-    fn generate() -> KeyResult<Pkcs8pemBytes> {
-        let ec_group = match EcGroup::from_curve_name(Nid::SECP256K1) {
-            Ok(group) => group,
-            Err(_) => return Err(KeyError::GenerationError(1000)),
-        };
-
-        let ec_key = match EcKey::generate(&ec_group) {
+    /// Get the public key as a compressed SEC1 point
+    fn compressed_point(&self) -> KeyResult<Vec<u8>> {
+        let public_key = match Active::public_key(&self.pkcs8pem.public) {
             Ok(key) => key,
-            Err(_) => return Err(KeyError::GenerationError(1001)),
+            Err(_) => return Err(KeyError::StringifyError(3008)),
         };
 
-        let pkey = match PKey::from_ec_key(ec_key) {
-            Ok(pkey) => pkey,
-            Err(_) => return Err(KeyError::GenerationError(1001)),
-        };
+        Active::compressed_point(&public_key)
+    }
 
-        let mut pkcs8pem = Pkcs8pemBytes::new(&vec![], &vec![]);
+    /// Create a public key PEM holding the compressed point
+    ///
+    /// The backends always write the uncompressed point, so the SubjectPublicKeyInfo
+    /// is rebuilt with the same algorithm identifier and the compressed point.
+    fn compressed_public_pem(&self) -> KeyResult<String> {
+        let point = self.compressed_point()?;
 
-        pkcs8pem.private = match pkey.private_key_to_pem_pkcs8() {
-            Ok(bytes) => bytes,
-            Err(_) => return Err(KeyError::GenerationError(1002)),
+        let der = match Active::public_key(&self.pkcs8pem.public)
+            .and_then(|key| Active::public_der(&key))
+        {
+            Ok(der) => der,
+            Err(_) => return Err(KeyError::StringifyError(3009)),
         };
 
-        // Get the public key PEM
-        pkcs8pem.public = match pkey.public_key_to_pem() {
-            Ok(bytes) => bytes,
-            Err(_) => return Err(KeyError::GenerationError(1003)),
-        };
+        // SEQUENCE { AlgorithmIdentifier, BIT STRING }, short form lengths for the supported curves
+        if der.len() < 4 || der[0] != 0x30 || der[1] >= 0x80 || der[2] != 0x30 || der[3] >= 0x80 {
+            return Err(KeyError::StringifyError(3010));
+        }
+
+        let algorithm = &der[2..4 + der[3] as usize];
+
+        let mut bit_string = vec![0x03, point.len() as u8 + 1, 0x00];
+        bit_string.extend_from_slice(&point);
+
+        let mut spki = vec![0x30, (algorithm.len() + bit_string.len()) as u8];
+        spki.extend_from_slice(algorithm);
+        spki.extend_from_slice(&bit_string);
+
+        let encoded = base64::encode(&spki);
+
+        let mut pem = String::from("-----BEGIN PUBLIC KEY-----\n");
+        for line in encoded.as_bytes().chunks(64) {
+            pem.push_str(&String::from_utf8_lossy(line));
+            pem.push('\n');
+        }
+        pem.push_str("-----END PUBLIC KEY-----\n");
 
-        Ok(pkcs8pem)
+        Ok(pem)
     }
 
-    /// Get the PEM keypair in byte form
-    fn get_keypair(&self) -> KeyResult<PKey<Private>> {
-        let keypair = match EcKey::private_key_from_pem(&self.pkcs8pem.private) {
-            Ok(keypair) => keypair,
-            Err(_) => return Err(KeyError::SigningError(2007)),
-        };
+    /// Generate the PEM
+    fn generate(curve: Curve) -> KeyResult<Pkcs8pemBytes> {
+        Active::generate_ec(curve)
+    }
 
-        let keypair = match PKey::from_ec_key(keypair) {
-            Ok(keypair) => keypair,
-            Err(_) => return Err(KeyError::SigningError(2008)),
-        };
+    /// Verify a raw signature, keys without a private key use the public key
+    fn verify_bytes_with_hash(
+        &self,
+        data: &str,
+        signature: &[u8],
+        hash: HashAlg,
+    ) -> KeyResult<bool> {
+        let verifier = self
+            .parsed
+            .verifier(&self.pkcs8pem.public, || self.parse_keypair())?;
 
-        Ok(keypair)
+        Signing::verify_bytes(verifier, data, signature, hash)
+    }
+
+    /// Get the private key, it is parsed the first time it is used
+    fn get_keypair(&self) -> KeyResult<&PrivateKey> {
+        self.parsed.keypair(|| self.parse_keypair())
+    }
+
+    /// Parse the private PEM
+    fn parse_keypair(&self) -> KeyResult<PrivateKey> {
+        Signing::parse_keypair(&self.pkcs8pem, KeyKind::Ec)
+    }
+}
+
+// Key data is redacted so keys can't be leaked into logs
+impl fmt::Debug for EllipticCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        int_def::debug_key(f, "EllipticCurve", &self.name, &self.pkcs8pem)
+    }
+}
+
+impl Key for EllipticCurve {
+    fn sign(&self, data: &str) -> KeyResult<String> {
+        EllipticCurve::sign(self, data)
+    }
+
+    fn verify(&self, data: &str, signature: &str) -> KeyResult<bool> {
+        EllipticCurve::verify(self, data, signature)
+    }
+
+    fn get_pem(&self) -> KeyResult<Pkcs8pem> {
+        EllipticCurve::get_pem(self)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn key_type(&self) -> &str {
+        // A key that cannot be parsed fails to sign, so the type is only a default
+        // for a transaction that is never sent
+        match self.curve() {
+            Ok(Curve::P256) => "prime256v1",
+            Ok(Curve::Secp256k1) | Err(_) => "secp256k1",
+        }
+    }
+}
+
+impl Signer for EllipticCurve {
+    fn sign(&self, data: &[u8]) -> KeyResult<Vec<u8>> {
+        Signing::sign_to_bytes(self.get_keypair()?, data, HashAlg::default())
+    }
+
+    fn public_pem(&self) -> KeyResult<String> {
+        Ok(EllipticCurve::get_pem(self)?.public)
     }
 }
 
@@ -284,4 +849,362 @@ mod test {
 
         assert!(key.verify(&data_to_sign, &signature).unwrap());
     }
+
+    #[test]
+    fn ec_validate() {
+        let key = EllipticCurve::new("Test").unwrap();
+        key.validate().unwrap();
+
+        let pem = key.get_pem().unwrap();
+        let other_pem = EllipticCurve::new("Other").unwrap().get_pem().unwrap();
+
+        let bad_private =
+            EllipticCurve::create_from_pem("Test", &Pkcs8pem::new("invalid", &pem.public));
+        assert!(matches!(
+            bad_private.validate(),
+            Err(KeyError::ValidationError(6000))
+        ));
+
+        let bad_public =
+            EllipticCurve::create_from_pem("Test", &Pkcs8pem::new(&pem.private, "invalid"));
+        assert!(matches!(
+            bad_public.validate(),
+            Err(KeyError::ValidationError(6001))
+        ));
+
+        let mismatched =
+            EllipticCurve::create_from_pem("Test", &Pkcs8pem::new(&pem.private, &other_pem.public));
+        assert!(matches!(
+            mismatched.validate(),
+            Err(KeyError::ValidationError(6002))
+        ));
+    }
+
+    #[test]
+    fn ec_p256_sign() {
+        let data_to_sign = String::from("Test data");
+
+        let key = EllipticCurve::new_with_curve("Test", Curve::P256).unwrap();
+
+        let signature = key.sign(&data_to_sign).unwrap();
+
+        assert!(key.verify(&data_to_sign, &signature).unwrap());
+        assert!(!key.verify("Other data", &signature).unwrap());
+    }
+
+    #[test]
+    fn ec_curve_from_pem() {
+        let key = EllipticCurve::new("Test").unwrap();
+        assert_eq!(key.curve().unwrap(), Curve::Secp256k1);
+
+        let p256 = EllipticCurve::new_with_curve("Test", Curve::P256).unwrap();
+        let key = EllipticCurve::create_from_pem("Test", &p256.get_pem().unwrap());
+        assert_eq!(key.curve().unwrap(), Curve::P256);
+
+        let signature = p256.sign("Test data").unwrap();
+        assert!(key.verify("Test data", &signature).unwrap());
+
+        let public = p256.get_pem().unwrap().public;
+        let public_only = EllipticCurve::create_from_pem("Test", &Pkcs8pem::new("", &public));
+        assert_eq!(public_only.curve().unwrap(), Curve::P256);
+        assert_eq!(Key::key_type(&public_only), "prime256v1");
+        assert_eq!(
+            Key::key_type(&EllipticCurve::new("Test").unwrap()),
+            "secp256k1"
+        );
+    }
+
+    #[test]
+    fn ec_sign_sha512() {
+        let key = EllipticCurve::new("Test").unwrap();
+
+        let signature = key.sign_with_hash("Test data", HashAlg::Sha512).unwrap();
+
+        assert!(key
+            .verify_with_hash("Test data", &signature, HashAlg::Sha512)
+            .unwrap());
+        assert!(!key.verify("Test data", &signature).unwrap_or(false));
+    }
+
+    #[test]
+    fn ec_verify_detailed() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let signature = key.sign("Test data").unwrap();
+
+        let outcome = key.verify_detailed("Test data", &signature).unwrap();
+        assert_eq!(outcome, VerifyOutcome::Valid);
+
+        let outcome = key.verify_detailed("Other data", &signature).unwrap();
+        assert_eq!(outcome, VerifyOutcome::Invalid);
+
+        let outcome = key.verify_detailed("Test data", "not base64!").unwrap();
+        assert_eq!(outcome, VerifyOutcome::MalformedSignature);
+    }
+
+    #[test]
+    fn ec_sign_cached() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let signature = key.sign("Test data").unwrap();
+
+        let cloned = key.clone();
+        let cloned_signature = cloned.sign("Test data").unwrap();
+
+        assert!(key.verify("Test data", &cloned_signature).unwrap());
+        assert!(cloned.verify("Test data", &signature).unwrap());
+
+        // The parsed key can be used from another thread
+        let handle = std::thread::spawn(move || cloned.sign("Test data"));
+        assert!(handle.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn ec_verify_cached() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let signature = key.sign("Test data").unwrap();
+
+        assert!(key.verify("Test data", &signature).unwrap());
+
+        let cloned = key.clone();
+        assert!(cloned.verify("Test data", &signature).unwrap());
+        assert!(!cloned.verify("Other data", &signature).unwrap());
+
+        // A key that can't be parsed fails every time
+        let public = key.get_pem().unwrap().public;
+        let invalid = EllipticCurve::create_from_pem("Test", &Pkcs8pem::new("invalid", &public));
+
+        for _ in 0..2 {
+            assert_eq!(
+                invalid.verify("Test data", &signature),
+                Err(KeyError::SigningError(2007))
+            );
+        }
+    }
+
+    #[test]
+    fn ec_public_only() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let signature = key.sign("Test data").unwrap();
+
+        let public = key.get_pem().unwrap().public;
+        let public_only = EllipticCurve::create_from_pem("Test", &Pkcs8pem::new("", &public));
+
+        assert!(!public_only.has_private_key());
+        assert_eq!(
+            public_only.sign("Test data"),
+            Err(KeyError::SigningError(2009))
+        );
+        assert!(public_only.verify("Test data", &signature).unwrap());
+        assert_eq!(
+            public_only.verify_detailed("Other data", &signature),
+            Ok(VerifyOutcome::Invalid)
+        );
+    }
+
+    #[test]
+    fn ec_raw_signature() {
+        // r is all 0x01 bytes and s is all 0x02 bytes
+        let mut der = vec![0x30, 0x44, 0x02, 0x20];
+        der.extend_from_slice(&[0x01; 32]);
+        der.extend_from_slice(&[0x02, 0x20]);
+        der.extend_from_slice(&[0x02; 32]);
+
+        let raw = EllipticCurve::der_to_raw(&der).unwrap();
+        assert_eq!(&raw[..32], &[0x01; 32]);
+        assert_eq!(&raw[32..], &[0x02; 32]);
+
+        assert_eq!(EllipticCurve::raw_to_der(&raw).unwrap(), der);
+
+        assert_eq!(
+            EllipticCurve::raw_to_der(&raw[1..]),
+            Err(KeyError::SigningError(2011))
+        );
+        assert_eq!(
+            EllipticCurve::der_to_raw(&raw),
+            Err(KeyError::SigningError(2011))
+        );
+    }
+
+    #[test]
+    fn ec_sign_raw() {
+        let ec = EllipticCurve::new("Test").unwrap();
+
+        let signature = ec
+            .sign_with_format("Test data", SignatureFormat::Raw)
+            .unwrap();
+        assert_eq!(base64::decode(&signature).unwrap().len(), 64);
+
+        assert!(ec
+            .verify_with_format("Test data", &signature, SignatureFormat::Raw)
+            .unwrap());
+        assert!(!ec
+            .verify_with_format("Other data", &signature, SignatureFormat::Raw)
+            .unwrap());
+
+        let signature = ec
+            .sign_with_format("Test data", SignatureFormat::Der)
+            .unwrap();
+        assert!(ec.verify("Test data", &signature).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn ec_to_pkey() {
+        let ec = EllipticCurve::new("Test").unwrap();
+        let pkey = ec.to_pkey().unwrap();
+
+        assert!(pkey.ec_key().unwrap().check_key().is_ok());
+        assert_eq!(pkey.private_key_to_der().unwrap(), ec.get_der().unwrap().0);
+    }
+
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn ec_der() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let (private_der, public_der) = key.get_der().unwrap();
+
+        let private = PKey::private_key_from_der(&private_der).unwrap();
+        let public = PKey::public_key_from_der(&public_der).unwrap();
+        let pem = Pkcs8pem::new(
+            str::from_utf8(&private.private_key_to_pem_pkcs8().unwrap()).unwrap(),
+            str::from_utf8(&public.public_key_to_pem().unwrap()).unwrap(),
+        );
+
+        let converted = EllipticCurve::create_from_pem("Converted", &pem);
+        let signature = converted.sign("Test data").unwrap();
+        assert!(key.verify("Test data", &signature).unwrap());
+        assert_eq!(converted.get_fingerprint(), key.get_fingerprint());
+    }
+
+    #[test]
+    fn ec_sign_prehashed() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let digest = Active::sha256(b"Test data");
+
+        let signature = key.sign_prehashed(&digest).unwrap();
+        assert!(key.verify("Test data", &signature).unwrap());
+
+        let result = key.sign_prehashed(b"Not a digest");
+        assert!(matches!(result, Err(KeyError::SigningError(2010))));
+    }
+
+    #[test]
+    fn ec_sign_bytes() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let signature = key.sign_to_bytes("Test data").unwrap();
+
+        assert!(key.verify_bytes_sig("Test data", &signature).unwrap());
+        assert!(!key.verify_bytes_sig("Other data", &signature).unwrap());
+        assert!(key
+            .verify("Test data", &base64::encode(&signature))
+            .unwrap());
+    }
+
+    #[test]
+    fn ec_fingerprint() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let fingerprint = key.get_fingerprint().unwrap();
+
+        assert_eq!(fingerprint.len(), 95);
+        let same = EllipticCurve::create_from_pem("Same", &key.get_pem().unwrap());
+        assert_eq!(fingerprint, same.get_fingerprint().unwrap());
+
+        let other = EllipticCurve::new("Other").unwrap();
+        assert_ne!(fingerprint, other.get_fingerprint().unwrap());
+    }
+
+    #[test]
+    fn ec_from_seed() {
+        let key = EllipticCurve::from_seed("Test", &[7; 32]).unwrap();
+        let same = EllipticCurve::from_seed("Test", &[7; 32]).unwrap();
+        let other = EllipticCurve::from_seed("Test", &[8; 32]).unwrap();
+
+        key.validate().unwrap();
+        assert_eq!(
+            key.get_pem().unwrap().private,
+            same.get_pem().unwrap().private
+        );
+        assert_ne!(
+            key.get_fingerprint().unwrap(),
+            other.get_fingerprint().unwrap()
+        );
+
+        let signature = key.sign("Test data").unwrap();
+        assert!(same.verify("Test data", &signature).unwrap());
+
+        let result = EllipticCurve::from_seed("Test", &[0; 32]);
+        assert!(matches!(result, Err(KeyError::GenerationError(1007))));
+    }
+
+    #[test]
+    fn ec_generate_batch() {
+        let keys = EllipticCurve::generate_batch(&["first", "second"]);
+
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].as_ref().unwrap().name, "first");
+        assert_eq!(keys[1].as_ref().unwrap().name, "second");
+    }
+
+    #[test]
+    fn ec_compressed_public() {
+        let private = std::fs::read_to_string("./testfiles/ec_private.pem").unwrap();
+        let public = std::fs::read_to_string("./testfiles/ec_public.pem").unwrap();
+        let key = EllipticCurve::create_from_pem("Test", &Pkcs8pem::new(&private, &public));
+
+        assert_eq!(
+            key.get_compressed_public().unwrap(),
+            "02c5bbddbddc414b3c0075b2e3f1a4ffeeb5d1bf1e8f1fe3f54c2be6fcb5e941dd"
+        );
+    }
+
+    #[test]
+    fn ec_compressed_pem() {
+        let private = std::fs::read_to_string("./testfiles/ec_private.pem").unwrap();
+        let public = std::fs::read_to_string("./testfiles/ec_public.pem").unwrap();
+        let key = EllipticCurve::create_from_pem("Test", &Pkcs8pem::new(&private, &public));
+
+        let pem = key
+            .get_pem_with_point_format(PointFormat::Uncompressed)
+            .unwrap();
+        assert_eq!(pem.public, public);
+
+        let pem = key
+            .get_pem_with_point_format(PointFormat::Compressed)
+            .unwrap();
+        assert_eq!(pem.private, private);
+
+        // OpenSSL reads the compressed point back as the same key
+        let reloaded = EllipticCurve::create_from_pem("Test", &Pkcs8pem::new("", &pem.public));
+        assert_eq!(
+            reloaded.get_compressed_public().unwrap(),
+            key.get_compressed_public().unwrap()
+        );
+
+        let signature = key.sign("Test data").unwrap();
+        assert!(crate::key::verify_with_public(&pem.public, "Test data", &signature).unwrap());
+
+        // The algorithm identifier, the BIT STRING header and the 33 byte point
+        let body: String = pem
+            .public
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        let spki = base64::decode(&body).unwrap();
+        assert_eq!(spki.len(), 2 + 18 + 3 + 33);
+        assert_eq!(spki[spki.len() - 33], 0x02);
+    }
+
+    #[test]
+    fn ec_debug_redacted() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let pem = key.get_pem().unwrap();
+        let private_line = pem.private.lines().nth(1).unwrap();
+
+        let debug = format!("{:?}", key);
+        assert!(debug.contains("[REDACTED]"));
+        assert!(debug.contains(&key.get_fingerprint().unwrap()));
+        assert!(!debug.contains(private_line));
+
+        let debug = format!("{:?}", key.pkcs8pem);
+        assert!(!debug.contains(private_line));
+    }
 }
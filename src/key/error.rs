@@ -30,13 +30,14 @@ use std::fmt;
 pub type KeyResult<T> = Result<T, KeyError>;
 
 /// KeyError data holder
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum KeyError {
     GenerationError(u16), // 1000
     SigningError(u16),    // 2000
     StringifyError(u16),  // 3000
     ImportError(u16),     // 4000
     ExportError(u16),     // 5000
+    ValidationError(u16), // 6000
 }
 
 impl fmt::Display for KeyError {
@@ -62,6 +63,10 @@ impl fmt::Display for KeyError {
                 let error = KeyErrorHandler::get_export_error(code);
                 write!(f, "Export Error - {}: {}", code, error)
             }
+            KeyError::ValidationError(ref code) => {
+                let error = KeyErrorHandler::get_validation_error(code);
+                write!(f, "Validation Error - {}: {}", code, error)
+            }
         }
     }
 }
@@ -78,6 +83,7 @@ impl KeyErrorHandler {
             1001 => "Error generating Elliptic Curve Keypair",
             1002 => "Error generating Elliptic Curve Private PEM",
             1003 => "Error generating Elliptic Curve Public PEM",
+            1007 => "The seed is not a valid Elliptic Curve private key",
 
             // RSA
             1004 => "Error generating the RSA Key",
@@ -98,6 +104,9 @@ impl KeyErrorHandler {
             2006 => "Signature verification failed",
             2007 => "Error initialising private key",
             2008 => "Error initialising public key",
+            2009 => "No private key available",
+            2010 => "The digest is not a SHA-256 digest",
+            2011 => "Invalid ECDSA signature encoding",
             _ => "Unknown Error",
         }
     }
@@ -108,6 +117,10 @@ impl KeyErrorHandler {
             3001 => "Error converting public pem to string",
             3007 => "Error initialising private key",
             3008 => "Error initialising public key",
+            3009 => "Error converting public key to DER",
+            3010 => "Error converting public key to compressed form",
+            3011 => "Error converting private key to DER",
+            3012 => "Error converting private key to the requested format",
             _ => "Unknown Error",
         }
     }
@@ -117,6 +130,18 @@ impl KeyErrorHandler {
             4000 => "Error opening file for import",
             4001 => "Error reading file contents",
             4002 => "Key Type missmatch",
+            4003 => "Unable to decrypt the private key, check the passphrase",
+            4004 => "Error converting the decrypted private key to PEM",
+            4005 => "The private key is encrypted, import it with a passphrase",
+            4006 => "The key uses an unsupported elliptic curve",
+            4007 => "Error parsing the private key PEM",
+            4008 => "Error deriving the public key from the private key",
+            4009 => "Unknown key type, expected rsa or ec",
+            4010 => "The file is not a PKCS#12 bundle",
+            4011 => "The PKCS#12 bundle does not contain a private key",
+            4012 => "The private key is not a PEM",
+            4013 => "The public key is not a PEM",
+            4014 => "The file is not valid key JSON",
             _ => "Unknown Error",
         }
     }
@@ -126,6 +151,18 @@ impl KeyErrorHandler {
             5000 => "Error generating JSON",
             5001 => "Error preparing the export file for writing",
             5002 => "Error writing to the export file",
+            5003 => "Error encrypting the private key",
+            5004 => "The key name can't be used as a file name",
+            5005 => "The export file already exists",
+            _ => "Unknown Error",
+        }
+    }
+
+    fn get_validation_error(code: &u16) -> &str {
+        match code {
+            6000 => "Invalid private key PEM",
+            6001 => "Invalid public key PEM",
+            6002 => "The private and public keys do not match",
             _ => "Unknown Error",
         }
     }
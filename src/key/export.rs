@@ -63,16 +63,31 @@
 //!     }
 //! }
 //! ```
+//!
+//! Keys exported with a passphrase also have `"encrypted": true` set,
+//! the private PEM is encrypted and the public PEM is left as is.
+//! Exporting with a passphrase requires the `openssl` feature.
+//! The JSON is compact, `export_rsa_pretty()` and `export_ec_pretty()` write it
+//! indented instead.
+//!
+//! ## Exporting to a Directory
+//! `export_to_dir()` names the file after the key, as `<name>.<type>.json`,
+//! and doesn't replace existing files unless asked to.
 
 extern crate serde_json;
 
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "openssl")]
+use openssl::pkey::PKey;
+#[cfg(feature = "openssl")]
+use openssl::symm::Cipher;
 
 use super::{
     error::{KeyError, KeyResult},
-    EllipticCurve, Pkcs8pem, RSA,
+    EllipticCurve, Key, Pkcs8pem, PointFormat, RSA,
 };
 
 /// Export an RSA key to the specified location.Key.
@@ -97,7 +112,62 @@ use super::{
 pub fn export_rsa(key: &RSA, path: &str) -> KeyResult<()> {
     let pem = key.get_pem()?;
 
-    export(&key.name, &pem, &path, "rsa")?;
+    export(&key.name, &pem, Path::new(path), "rsa", false, true, false)?;
+
+    Ok(())
+}
+
+/// Export an RSA key as indented JSON, which is easier to read and diff.
+///
+/// The file is otherwise the same as one written by `export_rsa()`.
+///
+/// ## Example
+/// ```
+/// # use activeledger::key::RSA;
+/// # use activeledger::key::export;
+/// let rsa = RSA::new("Key name").unwrap();
+/// let path = "export/path/file.json";
+/// # let path = std::env::temp_dir().join("activeledger_rsa_pretty.json");
+/// # let path = path.to_str().unwrap();
+///
+/// export::export_rsa_pretty(&rsa, path).unwrap();
+/// ```
+///
+/// ## Errors
+/// If this function cannot export the given key it will return an ExportError.
+pub fn export_rsa_pretty(key: &RSA, path: &str) -> KeyResult<()> {
+    let pem = key.get_pem()?;
+
+    export(&key.name, &pem, Path::new(path), "rsa", false, true, true)?;
+
+    Ok(())
+}
+
+/// Export an RSA key to the specified location with the private key encrypted.
+///
+/// The private PEM is encrypted with AES-256-CBC using the given passphrase.
+/// The file can be imported using `import::import_rsa_encrypted()`.
+/// Requires the `openssl` feature.
+///
+/// ## Example
+/// ```
+/// # use activeledger::key::RSA;
+/// # use activeledger::key::export;
+/// let rsa = RSA::new("Key name").unwrap();
+/// let path = "export/path/file.json";
+/// # let path = std::env::temp_dir().join("activeledger_rsaexport_encrypted.json");
+/// # let path = path.to_str().unwrap();
+///
+/// export::export_rsa_encrypted(&rsa, path, "passphrase").unwrap();
+/// ```
+///
+/// ## Errors
+/// If this function cannot export the given key it will return an ExportError.
+#[cfg(feature = "openssl")]
+pub fn export_rsa_encrypted(key: &RSA, path: &str, passphrase: &str) -> KeyResult<()> {
+    let pem = encrypt_pem(&key.get_pem()?, passphrase)?;
+
+    export(&key.name, &pem, Path::new(path), "rsa", true, true, false)?;
 
     Ok(())
 }
@@ -124,13 +194,179 @@ pub fn export_rsa(key: &RSA, path: &str) -> KeyResult<()> {
 pub fn export_ec(key: &EllipticCurve, path: &str) -> KeyResult<()> {
     let pem = key.get_pem()?;
 
-    export(&key.name, &pem, &path, "ec")?;
+    export(&key.name, &pem, Path::new(path), "ec", false, true, false)?;
+
+    Ok(())
+}
+
+/// Export an EC key as indented JSON, which is easier to read and diff.
+///
+/// The file is otherwise the same as one written by `export_ec()`.
+///
+/// ## Example
+/// ```
+/// # use activeledger::key::EllipticCurve;
+/// # use activeledger::key::export;
+/// let ec = EllipticCurve::new("Key name").unwrap();
+/// let path = "export/path/file.json";
+/// # let path = std::env::temp_dir().join("activeledger_ec_pretty.json");
+/// # let path = path.to_str().unwrap();
+///
+/// export::export_ec_pretty(&ec, path).unwrap();
+/// ```
+///
+/// ## Errors
+/// If this function cannot export the given key it will return an ExportError.
+pub fn export_ec_pretty(key: &EllipticCurve, path: &str) -> KeyResult<()> {
+    let pem = key.get_pem()?;
+
+    export(&key.name, &pem, Path::new(path), "ec", false, true, true)?;
+
+    Ok(())
+}
+
+/// Export an EC key with the public key point in the given format.
+///
+/// `export_ec()` uses the uncompressed format, some tools need the public
+/// key PEM to hold the compressed point instead.
+///
+/// ## Example
+/// ```
+/// # use activeledger::key::{EllipticCurve, PointFormat};
+/// # use activeledger::key::export;
+/// let ec = EllipticCurve::new("Key name").unwrap();
+/// let path = "export/path/file.json";
+/// # let path = std::env::temp_dir().join("activeledger_ecexport_compressed.json");
+/// # let path = path.to_str().unwrap();
+///
+/// export::export_ec_with_point_format(&ec, path, PointFormat::Compressed).unwrap();
+/// ```
+///
+/// ## Errors
+/// If this function cannot export the given key it will return an ExportError,
+/// or a StringifyError if the public key can't be converted.
+pub fn export_ec_with_point_format(
+    key: &EllipticCurve,
+    path: &str,
+    format: PointFormat,
+) -> KeyResult<()> {
+    let pem = key.get_pem_with_point_format(format)?;
+
+    export(&key.name, &pem, Path::new(path), "ec", false, true, false)?;
+
+    Ok(())
+}
+
+/// Export an EC key to the specified location with the private key encrypted.
+///
+/// The private PEM is encrypted with AES-256-CBC using the given passphrase.
+/// The file can be imported using `import::import_ec_encrypted()`.
+/// Requires the `openssl` feature.
+///
+/// ## Example
+/// ```
+/// # use activeledger::key::EllipticCurve;
+/// # use activeledger::key::export;
+/// let ec = EllipticCurve::new("Key name").unwrap();
+/// let path = "export/path/file.json";
+/// # let path = std::env::temp_dir().join("activeledger_ecexport_encrypted.json");
+/// # let path = path.to_str().unwrap();
+///
+/// export::export_ec_encrypted(&ec, path, "passphrase").unwrap();
+/// ```
+///
+/// ## Errors
+/// If this function cannot export the given key it will return an ExportError.
+#[cfg(feature = "openssl")]
+pub fn export_ec_encrypted(key: &EllipticCurve, path: &str, passphrase: &str) -> KeyResult<()> {
+    let pem = encrypt_pem(&key.get_pem()?, passphrase)?;
+
+    export(&key.name, &pem, Path::new(path), "ec", true, true, false)?;
 
     Ok(())
 }
 
+/// Export a key into a directory, naming the file after the key
+///
+/// The file is named `<name>.<type>.json`, where the type is `rsa` or `ec`,
+/// and the path of the file is returned. Existing files are only replaced
+/// when `overwrite` is true, so exporting many keys can't replace an earlier one.
+///
+/// ## Example
+/// ```
+/// # use activeledger::key::RSA;
+/// # use activeledger::key::export;
+/// let rsa = RSA::new("keyname").unwrap();
+/// let dir = "export/path";
+/// # let dir = std::env::temp_dir();
+/// # let dir = dir.to_str().unwrap();
+///
+/// // Writes export/path/keyname.rsa.json
+/// let path = export::export_to_dir(&rsa, dir, true).unwrap();
+/// ```
+///
+/// ## Errors
+/// Returns ExportError 5004 if the key name is empty or contains a path separator
+/// and 5005 if the file exists and `overwrite` is false.
+pub fn export_to_dir(key: &dyn Key, dir: &str, overwrite: bool) -> KeyResult<PathBuf> {
+    let name = key.name();
+
+    if name.is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(KeyError::ExportError(5004));
+    }
+
+    // EC keys are exported as ec whatever their curve
+    let key_type = match key.key_type() {
+        "rsa" => "rsa",
+        _ => "ec",
+    };
+
+    let path = Path::new(dir).join(format!("{}.{}.json", name, key_type));
+
+    export(
+        name,
+        &key.get_pem()?,
+        &path,
+        key_type,
+        false,
+        overwrite,
+        false,
+    )?;
+
+    Ok(path)
+}
+
+/// Encrypt the private PEM using the given passphrase
+#[cfg(feature = "openssl")]
+fn encrypt_pem(pem: &Pkcs8pem, passphrase: &str) -> KeyResult<Pkcs8pem> {
+    let pkey = match PKey::private_key_from_pem(pem.private.as_bytes()) {
+        Ok(pkey) => pkey,
+        Err(_) => return Err(KeyError::ExportError(5003)),
+    };
+
+    let private = match pkey
+        .private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), passphrase.as_bytes())
+    {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(KeyError::ExportError(5003)),
+    };
+
+    match String::from_utf8(private) {
+        Ok(private) => Ok(Pkcs8pem::new(&private, &pem.public)),
+        Err(_) => Err(KeyError::ExportError(5003)),
+    }
+}
+
 /// Create a JSON export using the given data
-fn export(name: &str, pem: &Pkcs8pem, path: &str, key_type: &str) -> KeyResult<()> {
+fn export(
+    name: &str,
+    pem: &Pkcs8pem,
+    path: &Path,
+    key_type: &str,
+    encrypted: bool,
+    overwrite: bool,
+    pretty: bool,
+) -> KeyResult<()> {
     // Build the structure of the file
     let key_file_data = r#"{
             "name":"",
@@ -152,24 +388,47 @@ fn export(name: &str, pem: &Pkcs8pem, path: &str, key_type: &str) -> KeyResult<(
     json_obj["pem"]["private"] = pem.private.to_string().into();
     json_obj["pem"]["public"] = pem.public.to_string().into();
 
-    let path = Path::new(path);
+    if encrypted {
+        json_obj["encrypted"] = true.into();
+    }
+
+    // Create the file instance, failing if it exists and can't be replaced
+    let mut options = OpenOptions::new();
+    options.write(true);
+
+    if overwrite {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
 
-    // Create the file instance
-    let mut file = match File::create(path) {
+    let mut file = match options.open(path) {
         Ok(file) => file,
+        Err(error) if error.kind() == ErrorKind::AlreadyExists => {
+            return Err(KeyError::ExportError(5005))
+        }
         Err(_) => return Err(KeyError::ExportError(5001)),
     };
 
-    // Write the data to the file and return
-    match file.write_all(json_obj.to_string().as_bytes()) {
-        Ok(_) => return Ok(()),
-        Err(_) => return Err(KeyError::ExportError(5002)),
+    let contents = if pretty {
+        match serde_json::to_string_pretty(&json_obj) {
+            Ok(contents) => contents,
+            Err(_) => return Err(KeyError::ExportError(5000)),
+        }
+    } else {
+        json_obj.to_string()
     };
+
+    // Write the data to the file and return
+    match file.write_all(contents.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(KeyError::ExportError(5002)),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::key::{export, EllipticCurve, RSA};
+    use crate::key::{export, import, EllipticCurve, KeyError, Pkcs8pem, PointFormat, RSA};
 
     #[test]
     fn export_rsa() {
@@ -178,10 +437,160 @@ mod tests {
         export::export_rsa(&key, "./testfiles/rsaexport.json").unwrap();
     }
 
+    #[test]
+    fn export_pretty() {
+        let key = RSA::new("Test").unwrap();
+        let file = std::env::temp_dir().join("activeledger_export_pretty.json");
+        let path = file.to_str().unwrap();
+
+        export::export_rsa_pretty(&key, path).unwrap();
+
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert!(contents.contains("\n  \"name\": \"Test\""));
+
+        let imported = import::import_rsa(path).unwrap();
+        assert_eq!(
+            key.get_fingerprint().unwrap(),
+            imported.get_fingerprint().unwrap()
+        );
+
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn export_public_only() {
+        let key = RSA::new("Test").unwrap();
+        let signature = key.sign("Test data").unwrap();
+
+        let public = key.get_pem().unwrap().public;
+        let public_only = RSA::create_from_pem("Test", &Pkcs8pem::new("", &public));
+
+        let file = std::env::temp_dir().join("activeledger_export_public_only.json");
+        let path = file.to_str().unwrap();
+
+        export::export_rsa(&public_only, path).unwrap();
+
+        let imported = import::import_rsa(path).unwrap();
+        assert!(!imported.has_private_key());
+        assert!(imported.verify("Test data", &signature).unwrap());
+
+        let key = EllipticCurve::new("Test").unwrap();
+        let signature = key.sign("Test data").unwrap();
+
+        let public = key.get_pem().unwrap().public;
+        let public_only = EllipticCurve::create_from_pem("Test", &Pkcs8pem::new("", &public));
+
+        export::export_ec(&public_only, path).unwrap();
+
+        let imported = import::import_ec(path).unwrap();
+        assert!(!imported.has_private_key());
+        assert!(imported.verify("Test data", &signature).unwrap());
+
+        std::fs::remove_file(file).unwrap();
+    }
+
     #[test]
     fn export_ec() {
         let key = EllipticCurve::new("Test").unwrap();
 
         export::export_ec(&key, "./testfiles/ecexport.json").unwrap();
     }
+
+    #[test]
+    fn export_ec_compressed() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let file = std::env::temp_dir().join("activeledger_export_ec_compressed.json");
+        let path = file.to_str().unwrap();
+
+        export::export_ec_with_point_format(&key, path, PointFormat::Compressed).unwrap();
+
+        let imported = import::import_ec(path).unwrap();
+        assert_eq!(
+            imported.get_compressed_public().unwrap(),
+            key.get_compressed_public().unwrap()
+        );
+        assert_ne!(
+            imported.get_pem().unwrap().public,
+            key.get_pem().unwrap().public
+        );
+
+        let signature = imported.sign("Test data").unwrap();
+        assert!(imported.verify("Test data", &signature).unwrap());
+
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn export_to_dir() {
+        let dir = std::env::temp_dir().join("activeledger_export_to_dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir_str = dir.to_str().unwrap();
+
+        let rsa = RSA::new("first").unwrap();
+        let ec = EllipticCurve::new("second").unwrap();
+
+        let rsa_path = export::export_to_dir(&rsa, dir_str, true).unwrap();
+        let ec_path = export::export_to_dir(&ec, dir_str, true).unwrap();
+
+        assert_eq!(rsa_path, dir.join("first.rsa.json"));
+        assert_eq!(ec_path, dir.join("second.ec.json"));
+
+        let imported = import::import_ec(ec_path.to_str().unwrap()).unwrap();
+        assert_eq!(imported.name, "second");
+
+        // The existing file is kept
+        let other = RSA::new("first").unwrap();
+        let result = export::export_to_dir(&other, dir_str, false);
+        assert!(matches!(result, Err(KeyError::ExportError(5005))));
+
+        let imported = import::import_rsa(rsa_path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            imported.get_fingerprint().unwrap(),
+            rsa.get_fingerprint().unwrap()
+        );
+
+        let mut unsafe_name = RSA::test_key();
+        unsafe_name.name = "../up".to_string();
+
+        let result = export::export_to_dir(&unsafe_name, dir_str, true);
+        assert!(matches!(result, Err(KeyError::ExportError(5004))));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn export_rsa_encrypted() {
+        let key = RSA::new("Test").unwrap();
+        let file = std::env::temp_dir().join("activeledger_export_rsa_encrypted.json");
+        let path = file.to_str().unwrap();
+
+        export::export_rsa_encrypted(&key, path, "Test passphrase").unwrap();
+
+        let imported = import::import_rsa_encrypted(path, "Test passphrase").unwrap();
+        let signature = imported.sign("Test data").unwrap();
+
+        assert!(key.verify("Test data", &signature).unwrap());
+        assert!(import::import_rsa(path).is_err());
+
+        std::fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "openssl")]
+    fn export_ec_encrypted() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let file = std::env::temp_dir().join("activeledger_export_ec_encrypted.json");
+        let path = file.to_str().unwrap();
+
+        export::export_ec_encrypted(&key, path, "Test passphrase").unwrap();
+
+        let imported = import::import_ec_encrypted(path, "Test passphrase").unwrap();
+        let signature = imported.sign("Test data").unwrap();
+
+        assert!(key.verify("Test data", &signature).unwrap());
+        assert!(import::import_ec(path).is_err());
+
+        std::fs::remove_file(file).unwrap();
+    }
 }
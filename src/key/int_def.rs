@@ -25,23 +25,56 @@
 //! The code in this file is intended to be used by code in this crate
 
 extern crate base64;
-extern crate openssl;
 
-use openssl::hash::MessageDigest;
-use openssl::pkey::{PKey, Private};
-use openssl::sign::{Signer, Verifier};
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::OnceLock;
 
 use base64::{decode, encode};
 
-use crate::key::{KeyError, KeyResult};
+use crate::key::{HashAlg, KeyError, KeyResult, VerifyOutcome};
+
+use super::backend::{Active, Backend, KeyKind, PrivateKey, PublicKey};
 
 /// PEM byte holder
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Pkcs8pemBytes {
     pub private: Vec<u8>,
     pub public: Vec<u8>,
 }
 
+/// Placeholder shown instead of private key data
+const REDACTED: &str = "[REDACTED]";
+
+// The private key is never included in debug output
+impl fmt::Debug for Pkcs8pemBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pkcs8pemBytes")
+            .field("private", &format_args!("{}", REDACTED))
+            .field("public", &String::from_utf8_lossy(&self.public))
+            .finish()
+    }
+}
+
+/// Debug output for a key, showing its name and fingerprint but not the private key
+pub fn debug_key(
+    f: &mut fmt::Formatter,
+    key_type: &str,
+    name: &str,
+    pkcs8pem: &Pkcs8pemBytes,
+) -> fmt::Result {
+    let fingerprint = match fingerprint(&pkcs8pem.public) {
+        Ok(fingerprint) => fingerprint,
+        Err(_) => String::from("Invalid public key"),
+    };
+
+    f.debug_struct(key_type)
+        .field("name", &name)
+        .field("fingerprint", &fingerprint)
+        .field("private", &format_args!("{}", REDACTED))
+        .finish()
+}
+
 impl Pkcs8pemBytes {
     /// Create a new PEM Byte holder object
     pub fn new(private: &[u8], public: &[u8]) -> Pkcs8pemBytes {
@@ -50,6 +83,120 @@ impl Pkcs8pemBytes {
             public: public.to_vec(),
         }
     }
+
+    /// Check if a private PEM is stored, keys created from a public PEM only have none
+    pub fn has_private(&self) -> bool {
+        !self.private.iter().all(u8::is_ascii_whitespace)
+    }
+}
+
+/// Keys parsed from the PEMs, kept so they are only parsed once
+///
+/// Parsing a PEM takes longer than verifying a signature. Each key is parsed
+/// the first time it's needed, clones share the parsed keys.
+#[derive(Clone, Default)]
+pub struct ParsedKeys {
+    keypair: OnceLock<PrivateKey>,
+    verifier: OnceLock<PublicKey>,
+}
+
+impl ParsedKeys {
+    /// Get the private key, parsing it with the given function the first time
+    pub fn keypair<F>(&self, parse: F) -> KeyResult<&PrivateKey>
+    where
+        F: FnOnce() -> KeyResult<PrivateKey>,
+    {
+        if let Some(keypair) = self.keypair.get() {
+            return Ok(keypair);
+        }
+
+        let keypair = parse()?;
+
+        Ok(self.keypair.get_or_init(|| keypair))
+    }
+
+    /// Get the public key used to verify signatures
+    ///
+    /// The public half of the private key is used when there is one, so signatures
+    /// are verified against the key that made them. Keys without a private key
+    /// use the public PEM.
+    pub fn verifier<F>(&self, public_pem: &[u8], parse: F) -> KeyResult<&PublicKey>
+    where
+        F: FnOnce() -> KeyResult<PrivateKey>,
+    {
+        if let Some(verifier) = self.verifier.get() {
+            return Ok(verifier);
+        }
+
+        let verifier = match self.keypair(parse) {
+            Ok(keypair) => Active::public_half(keypair)?,
+            Err(KeyError::SigningError(2009)) => Active::public_key(public_pem)?,
+            Err(error) => return Err(error),
+        };
+
+        Ok(self.verifier.get_or_init(|| verifier))
+    }
+}
+
+/// Create a fingerprint of a public key
+///
+/// The fingerprint is the SHA-256 hash of the DER encoded public key
+/// as colon separated hex.
+pub fn fingerprint(public_pem: &[u8]) -> KeyResult<String> {
+    let public_key = match Active::public_key(public_pem) {
+        Ok(key) => key,
+        Err(_) => return Err(KeyError::StringifyError(3008)),
+    };
+
+    let der = Active::public_der(&public_key)?;
+
+    let hex: Vec<String> = Active::sha256(&der)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    Ok(hex.join(":"))
+}
+
+/// Convert a keypair and its public PEM to DER
+///
+/// Returns the private DER and the public DER.
+pub fn to_der(keypair: &PrivateKey, public_pem: &[u8]) -> KeyResult<(Vec<u8>, Vec<u8>)> {
+    let private = Active::private_der(keypair)?;
+
+    let public_key = match Active::public_key(public_pem) {
+        Ok(key) => key,
+        Err(_) => return Err(KeyError::StringifyError(3008)),
+    };
+
+    let public = Active::public_der(&public_key)?;
+
+    Ok((private, public))
+}
+
+/// Generate a key for each name, keeping the order of the names
+///
+/// With the `rayon` feature the keys are generated in parallel.
+#[cfg(feature = "rayon")]
+pub fn generate_batch<K, F>(names: &[&str], generate: F) -> Vec<KeyResult<K>>
+where
+    K: Send,
+    F: Fn(&str) -> KeyResult<K> + Sync,
+{
+    use rayon::prelude::*;
+
+    names.par_iter().map(|name| generate(name)).collect()
+}
+
+/// Generate a key for each name, keeping the order of the names
+///
+/// With the `rayon` feature the keys are generated in parallel.
+#[cfg(not(feature = "rayon"))]
+pub fn generate_batch<K, F>(names: &[&str], generate: F) -> Vec<KeyResult<K>>
+where
+    F: Fn(&str) -> KeyResult<K>,
+{
+    names.iter().map(|name| generate(name)).collect()
 }
 
 /// Siging function holder
@@ -57,64 +204,116 @@ pub struct Signing;
 
 impl Signing {
     /// Sign given data using the given keypair and data
-    pub fn sign(keypair: &PKey<Private>, data: &str) -> KeyResult<String> {
-        // Create a signer
-        let mut signer = match Signer::new(MessageDigest::sha256(), &keypair) {
-            Ok(signer) => signer,
-            Err(_) => return Err(KeyError::SigningError(2000)),
-        };
+    pub fn sign(keypair: &PrivateKey, data: &str, hash: HashAlg) -> KeyResult<String> {
+        let signature_bytes = Signing::sign_to_bytes(keypair, data.as_bytes(), hash)?;
 
-        // Add data to signer
-        match signer.update(data.as_bytes()) {
-            Ok(_) => (),
-            Err(_) => {
-                return Err(KeyError::SigningError(2001));
-            }
-        };
+        Ok(encode(&signature_bytes))
+    }
 
-        // Get the signature as a vector of bytes
-        let signature_bytes = match signer.sign_to_vec() {
-            Ok(sig) => sig,
-            Err(_) => {
-                return Err(KeyError::SigningError(2002));
-            }
+    /// Sign given data and return the raw signature
+    pub fn sign_to_bytes(keypair: &PrivateKey, data: &[u8], hash: HashAlg) -> KeyResult<Vec<u8>> {
+        Active::sign(keypair, data, hash)
+    }
+
+    /// Sign a SHA-256 digest that has already been computed
+    ///
+    /// Gives the same signature as signing the data the digest was made from.
+    pub fn sign_prehashed(keypair: &PrivateKey, digest: &[u8]) -> KeyResult<String> {
+        let digest: &[u8; 32] = match digest.try_into() {
+            Ok(digest) => digest,
+            Err(_) => return Err(KeyError::SigningError(2010)),
         };
 
-        Ok(encode(&signature_bytes))
+        Ok(encode(&Active::sign_prehashed(keypair, digest)?))
     }
 
-    /// Verify a signature using the given keypair and data
-    pub fn verify(keypair: &PKey<Private>, data: &str, signature: &str) -> KeyResult<bool> {
-        // Decode the signature (base64 encoded)
-        let signature_bytes = match decode(signature) {
-            Ok(bytes) => bytes,
-            Err(_) => {
-                return Err(KeyError::SigningError(2003));
-            }
-        };
+    /// Verify a signature using the given key and data
+    pub fn verify(
+        keypair: &PublicKey,
+        data: &str,
+        signature: &str,
+        hash: HashAlg,
+    ) -> KeyResult<bool> {
+        let signature_bytes = Signing::decode_signature(signature)?;
 
-        // Initialise the OpenSSL verifier
-        let mut verifier = match Verifier::new(MessageDigest::sha256(), &keypair) {
-            Ok(verifier) => verifier,
-            Err(_) => {
-                return Err(KeyError::SigningError(2004));
-            }
-        };
+        Signing::verify_bytes(keypair, data, &signature_bytes, hash)
+    }
 
-        // Give it the data
-        match verifier.update(data.as_bytes()) {
-            Ok(_) => (),
-            Err(_) => {
-                return Err(KeyError::SigningError(2005));
+    /// Decode a base64 encoded signature
+    pub fn decode_signature(signature: &str) -> KeyResult<Vec<u8>> {
+        match decode(signature) {
+            Ok(bytes) => Ok(bytes),
+            Err(_) => Err(KeyError::SigningError(2003)),
+        }
+    }
+
+    /// Verify a raw signature using the given key and data
+    pub fn verify_bytes(
+        keypair: &PublicKey,
+        data: &str,
+        signature_bytes: &[u8],
+        hash: HashAlg,
+    ) -> KeyResult<bool> {
+        Active::verify(keypair, data.as_bytes(), signature_bytes, hash)
+    }
+
+    /// Turn a verification result into an outcome, separating mismatches from
+    /// malformed signatures
+    pub fn outcome(verified: KeyResult<bool>) -> KeyResult<VerifyOutcome> {
+        match verified {
+            Ok(true) => Ok(VerifyOutcome::Valid),
+            Ok(false) => Ok(VerifyOutcome::Invalid),
+            // The signature could not be decoded or parsed
+            Err(KeyError::SigningError(2003)) | Err(KeyError::SigningError(2006)) => {
+                Ok(VerifyOutcome::MalformedSignature)
             }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Check that the public PEM is valid and belongs to the given keypair
+    pub fn validate(keypair: &PrivateKey, public_pem: &[u8]) -> KeyResult<()> {
+        let public_key = match Active::public_key(public_pem) {
+            Ok(key) => key,
+            Err(_) => return Err(KeyError::ValidationError(6001)),
         };
 
-        // Run verification on the given signature
-        match verifier.verify(&signature_bytes) {
-            Ok(result) => return Ok(result),
-            Err(_) => {
-                return Err(KeyError::SigningError(2006));
-            }
+        // Sign with the private key and verify with the public key
+        let test_data = "activeledger key validation";
+
+        let signature = match Signing::sign(keypair, test_data, HashAlg::default()) {
+            Ok(signature) => signature,
+            Err(_) => return Err(KeyError::ValidationError(6000)),
         };
+
+        match Signing::verify(&public_key, test_data, &signature, HashAlg::default()) {
+            Ok(true) => Ok(()),
+            _ => Err(KeyError::ValidationError(6002)),
+        }
+    }
+
+    /// Verify a signature using only a public key PEM
+    pub fn verify_with_public(public_pem: &str, data: &str, signature: &str) -> KeyResult<bool> {
+        let public_key = Active::public_key(public_pem.as_bytes())?;
+
+        Signing::verify(&public_key, data, signature, HashAlg::default())
+    }
+
+    /// Parse the private PEM of a key, checking it is the expected type
+    ///
+    /// Returns SigningError 2009 if there is no private PEM and 2007 if it
+    /// can't be parsed or is another type of key.
+    pub fn parse_keypair(pkcs8pem: &Pkcs8pemBytes, kind: KeyKind) -> KeyResult<PrivateKey> {
+        if !pkcs8pem.has_private() {
+            return Err(KeyError::SigningError(2009));
+        }
+
+        let keypair = Active::private_key(&pkcs8pem.private)?;
+
+        if Active::kind(&keypair) != Some(kind) {
+            return Err(KeyError::SigningError(2007));
+        }
+
+        Ok(keypair)
     }
 }
@@ -25,18 +25,185 @@
 //!
 //! The key module handles key generation, data signing, and key importing and exporting.
 //!
-//! Currently RSA and EC (SECP256K1) keys can be generated.
+//! Currently RSA and EC (SECP256K1 and P-256) keys can be generated.
 //! See the various modules below for more information.
+//!
+//! ## Backends
+//! Keys use openssl by default. With the `rustcrypto` feature they use the
+//! pure Rust `rsa`, `k256` and `p256` crates instead, which don't need a C
+//! compiler to build:
+//!
+//! ```toml
+//! activeledger = { version = "0.1", default-features = false, features = ["rustcrypto"] }
+//! ```
+//!
+//! Both backends read and write the same PEMs and signatures, so keys can move
+//! between builds using either one. Passphrase protected PEMs, PKCS#12 bundles
+//! and `to_pkey()` need openssl and are left out of builds without it.
+//!
+//! ### WebAssembly
+//! Built this way the crate compiles for `wasm32-unknown-unknown`, for generating
+//! keys and signing transactions in a browser. Random numbers come from the
+//! browser's `crypto.getRandomValues()`, through getrandom's `js` feature, so
+//! the module must be loaded with wasm-bindgen. `Connection` is not available,
+//! send signed transactions from JavaScript instead.
+//!
+//! ## Identities
+//! A key becomes an Activeledger identity once it has been onboarded.
+//! The identity (stream) id is generated by the node while it processes the
+//! onboard transaction, it is not a hash of the public key. Onboarding the same
+//! key twice creates two different streams, so the id cannot be computed
+//! before the transaction is sent. Read it from the `$streams` section of the
+//! node's response instead.
+//!
+//! The `default` namespace only provides contracts for onboarding identities and
+//! managing namespaces and contracts, there is no built-in transaction to
+//! deactivate a stream. Streams are marked inactive by a contract in your own
+//! namespace, send its transaction with `Connection::send_transaction()`.
 
+mod backend;
 mod ec;
 mod error;
 pub mod export;
 pub mod import;
+mod int_def;
 mod rsa;
 
-pub use ec::EllipticCurve;
+pub use ec::{Curve, EllipticCurve, PointFormat, SignatureFormat};
 pub use error::{KeyError, KeyResult};
-pub use rsa::RSA;
+pub use rsa::{PrivateKeyFormat, RSA};
+
+use int_def::Signing;
+
+/// Functionality shared by all key types
+///
+/// Allows code to work with any key type, for example signing with a `&dyn Key`.
+///
+/// # Example
+/// ```
+/// # use activeledger::key::{EllipticCurve, Key, RSA};
+/// fn sign_with(key: &dyn Key) -> String {
+///     key.sign("Data to sign").unwrap()
+/// }
+///
+/// let rsa = RSA::new("keyname").unwrap();
+/// let ec = EllipticCurve::new("keyname").unwrap();
+///
+/// sign_with(&rsa);
+/// sign_with(&ec);
+/// ```
+pub trait Key {
+    /// Sign the given data
+    fn sign(&self, data: &str) -> KeyResult<String>;
+
+    /// Verify a signature against some data
+    fn verify(&self, data: &str, signature: &str) -> KeyResult<bool>;
+
+    /// Get a keys PEM as string values
+    fn get_pem(&self) -> KeyResult<Pkcs8pem>;
+
+    /// Get the name of the key
+    fn name(&self) -> &str;
+
+    /// Get the key type as used by Activeledger, for example when onboarding
+    ///
+    /// RSA keys are `rsa` and EC keys are named after their curve, `secp256k1`
+    /// or `prime256v1`. Activeledger nodes accept `rsa` and `secp256k1` keys.
+    fn key_type(&self) -> &str;
+
+    /// Sign the given data and get the public PEM of the key that signed it
+    ///
+    /// Returns the signature and the public PEM, which are usually needed
+    /// together when building a self signed transaction.
+    ///
+    /// # Example
+    /// ```
+    /// # use activeledger::key::{Key, RSA};
+    /// let key = RSA::new("keyname").unwrap();
+    ///
+    /// let (signature, public_pem) = key.sign_with_public("Data to sign").unwrap();
+    /// ```
+    fn sign_with_public(&self, data: &str) -> KeyResult<(String, String)> {
+        let signature = self.sign(data)?;
+        let public = self.get_pem()?.public;
+
+        Ok((signature, public))
+    }
+}
+
+/// An object safe signing interface for choosing a key backend at runtime
+///
+/// Implemented by `RSA` and `EllipticCurve`, other backends such as an HSM can
+/// implement it so signers can be stored as `Box<dyn Signer>`. Signatures are
+/// raw bytes, base64 encode them for a transaction's `$sigs`.
+///
+/// # Example
+/// ```
+/// # use activeledger::key::{self, EllipticCurve, Signer, RSA};
+/// let signers: Vec<Box<dyn Signer>> = vec![
+///     Box::new(RSA::new("keyname").unwrap()),
+///     Box::new(EllipticCurve::new("keyname").unwrap()),
+/// ];
+///
+/// for signer in &signers {
+///     let signature = base64::encode(&signer.sign(b"Data to sign").unwrap());
+///     let public_pem = signer.public_pem().unwrap();
+///
+///     assert!(key::verify_with_public(&public_pem, "Data to sign", &signature).unwrap());
+/// }
+/// ```
+pub trait Signer {
+    /// Sign the given data with SHA-256, returning the raw signature
+    fn sign(&self, data: &[u8]) -> KeyResult<Vec<u8>>;
+
+    /// Get the public PEM of the signing key
+    fn public_pem(&self) -> KeyResult<String>;
+}
+
+/// Hash algorithms that can be used when signing and verifying
+///
+/// Activeledger nodes verify SHA-256 signatures, other algorithms are for
+/// interoperating with other systems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlg {
+    /// SHA-256, used by default
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+/// The result of a detailed signature verification
+///
+/// Separates signatures that do not match the data from signatures that
+/// could not be read at all, see `verify_detailed` on `RSA` and `EllipticCurve`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyOutcome {
+    /// The signature matches the data
+    Valid,
+    /// The signature was read but does not match the data
+    Invalid,
+    /// The signature is not valid base64 or is not a valid signature for the key type
+    MalformedSignature,
+}
+
+/// Verify a signature using only a public key PEM
+///
+/// Works with both RSA and EC public keys, the key type is read from the PEM.
+/// This allows signatures from other identities to be verified when their
+/// private key is not available.
+///
+/// # Example
+/// ```
+/// # use activeledger::key::{self, RSA};
+/// # let rsa = RSA::new("keyname").unwrap();
+/// # let signature = rsa.sign("Signed data").unwrap();
+/// let public_pem = rsa.get_pem().unwrap().public;
+///
+/// let verified = key::verify_with_public(&public_pem, "Signed data", &signature).unwrap();
+/// ```
+pub fn verify_with_public(public_pem: &str, data: &str, signature: &str) -> KeyResult<bool> {
+    Signing::verify_with_public(public_pem, data, signature)
+}
 
 /// Holds the private and public PEMs as strings
 pub struct Pkcs8pem {
@@ -57,3 +224,66 @@ impl Pkcs8pem {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_public_rsa() {
+        let key = RSA::new("Test").unwrap();
+        let signature = key.sign("Test data").unwrap();
+        let public_pem = key.get_pem().unwrap().public;
+
+        assert!(verify_with_public(&public_pem, "Test data", &signature).unwrap());
+        assert!(!verify_with_public(&public_pem, "Other data", &signature).unwrap());
+    }
+
+    #[test]
+    fn verify_public_ec() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let signature = key.sign("Test data").unwrap();
+        let public_pem = key.get_pem().unwrap().public;
+
+        assert!(verify_with_public(&public_pem, "Test data", &signature).unwrap());
+    }
+
+    #[test]
+    fn sign_with_public() {
+        let key = EllipticCurve::new("Test").unwrap();
+        let (signature, public_pem) = key.sign_with_public("Test data").unwrap();
+
+        assert_eq!(public_pem, key.get_pem().unwrap().public);
+        assert!(verify_with_public(&public_pem, "Test data", &signature).unwrap());
+    }
+
+    #[test]
+    fn signer_trait_object() {
+        let signers: Vec<Box<dyn Signer>> = vec![
+            Box::new(RSA::new("Test").unwrap()),
+            Box::new(EllipticCurve::new("Test").unwrap()),
+        ];
+
+        for signer in &signers {
+            let signature = base64::encode(&signer.sign(b"Test data").unwrap());
+            let public_pem = signer.public_pem().unwrap();
+
+            assert!(verify_with_public(&public_pem, "Test data", &signature).unwrap());
+        }
+
+        let public = RSA::new("Test").unwrap().get_pem().unwrap().public;
+        let public_only: Box<dyn Signer> =
+            Box::new(RSA::create_from_pem("Test", &Pkcs8pem::new("", &public)));
+
+        assert_eq!(
+            public_only.sign(b"Test data"),
+            Err(KeyError::SigningError(2009))
+        );
+        assert_eq!(public_only.public_pem().unwrap(), public);
+    }
+
+    #[test]
+    fn verify_public_invalid_pem() {
+        assert!(verify_with_public("", "Test data", "").is_err());
+    }
+}
@@ -40,6 +40,28 @@
 //!
 //! Integrating these into this crate makes it much quicker to bootstrap your DLT software.
 //!
+//! ## Features
+//! * `network` - Enabled by default. Provides `Connection` and the HTTP client it
+//!   uses. Without it keys can be generated and transactions built and signed,
+//!   for example to sign on a device and send from elsewhere. Transactions are
+//!   encrypted for the node with openssl, so it also enables `openssl`.
+//! * `openssl` - Enabled by default. The key backend, openssl is built from source
+//!   and statically linked. Passphrase protected PEMs, PKCS#12 bundles and
+//!   `to_pkey()` are only available with it.
+//! * `rustcrypto` - A pure Rust key backend using the `rsa`, `k256` and `p256`
+//!   crates. Keys use it instead of openssl when it is enabled, without
+//!   `openssl` and `network` the crate has no C dependencies. See the key module.
+//!   That build also compiles for `wasm32-unknown-unknown`, so keys can be
+//!   generated and transactions signed in a browser.
+//! * `test-utils` - Provides `RSA::test_key()`, a fixed key for tests that don't
+//!   need a freshly generated one, and `EllipticCurve::from_seed()` for keys
+//!   derived from a seed. These keys are not secret, never use them outside of tests.
+//!   Also provides `MockConnection`, a `TransactionSender` that returns canned
+//!   responses so code that sends transactions can be tested without a node.
+//! * `rayon` - Generates the keys in `RSA::generate_batch()` and
+//!   `EllipticCurve::generate_batch()` in parallel, and encrypts the chunks of
+//!   large encrypted transactions in parallel.
+//!
 //! ## Additional Activeledger crates
 //! Adhearing to the Rust mentality of keeping things small we have created other crates that can be used in conjunction
 //! with this one to add additional functionality.
@@ -62,7 +84,18 @@
 //!
 //! [Report Issues](https://github.com/activeledger/SDK-Rust/issues)
 
+pub mod canonical;
 mod connection;
 pub mod key;
 
-pub use connection::{error, transaction::Transaction, Connection};
+pub use connection::{
+    error, response,
+    transaction::{SignatureBuilder, Transaction, TransactionBuilder},
+    TransactionSender,
+};
+
+#[cfg(feature = "network")]
+pub use connection::{Connection, ConnectionBuilder, OaepHash};
+
+#[cfg(feature = "test-utils")]
+pub use connection::MockConnection;